@@ -0,0 +1,417 @@
+// 一个最小的内存 KV 服务，redis-cli 和 redis-benchmark 可以直接连接：
+//
+//     cargo run --example kv_server -- 127.0.0.1:6380
+//     redis-cli -p 6380 set foo bar
+//     redis-benchmark -p 6380 -t set,get,incr -q
+//
+// 支持 GET、SET、DEL、PING、ECHO、EXISTS、INCR、EXPIRE，以及客户端启动时会发送的
+// COMMAND 和 CONFIG GET。每个连接一个线程，数据用 Mutex 保护
+
+use std::collections::HashMap;
+use std::io::BufWriter;
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::ser::{Serialize, Serializer};
+use serde_resp::info::{CommandInfo, CommandTable};
+use serde_resp::router::{Handler, Router};
+use serde_resp::{ErrorCode, Integer, RedisError, Simple};
+use serde_resp_derive::RespCommand;
+
+#[derive(RespCommand, Debug)]
+enum Request {
+    #[resp(flags = "fast")]
+    Ping(#[resp(optional)] Option<Vec<u8>>),
+    #[resp(flags = "fast")]
+    Echo(Vec<u8>),
+    #[resp(flags = "readonly fast")]
+    Get(#[resp(key)] Vec<u8>),
+    #[resp(flags = "write denyoom")]
+    Set {
+        #[resp(key)]
+        key: Vec<u8>,
+        value: Vec<u8>,
+        #[resp(flag)]
+        nx: bool,
+        #[resp(flag)]
+        xx: bool,
+        #[resp(name = "EX")]
+        ex: Option<i64>,
+        #[resp(name = "PX")]
+        px: Option<i64>,
+    },
+    #[resp(flags = "write")]
+    Del {
+        #[resp(key, variadic)]
+        keys: Vec<Vec<u8>>,
+    },
+    #[resp(flags = "readonly fast")]
+    Exists {
+        #[resp(key, variadic)]
+        keys: Vec<Vec<u8>>,
+    },
+    #[resp(flags = "write denyoom fast")]
+    Incr(#[resp(key)] Vec<u8>),
+    #[resp(flags = "write fast")]
+    Expire(#[resp(key)] Vec<u8>, i64),
+}
+
+// 服务自身的信息，客户端连接时会查询
+#[derive(RespCommand, Debug)]
+enum Admin {
+    #[resp(name = "COMMAND", flags = "loading stale")]
+    Command,
+    #[resp(name = "COMMAND COUNT", flags = "loading stale")]
+    CommandCount,
+    #[resp(name = "COMMAND INFO", flags = "loading stale")]
+    CommandInfo(#[resp(optional, variadic)] Vec<String>),
+    #[resp(name = "COMMAND DOCS", flags = "loading stale")]
+    CommandDocs(#[resp(optional, variadic)] Vec<String>),
+    #[resp(name = "CONFIG GET", flags = "admin loading stale")]
+    ConfigGet(#[resp(variadic)] Vec<String>),
+}
+
+enum Reply {
+    Simple(&'static str),
+    Bulk(Option<Vec<u8>>),
+    Integer(i64),
+}
+
+impl Serialize for Reply {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Reply::Simple(s) => Simple(s).serialize(serializer),
+            Reply::Bulk(Some(b)) => serializer.serialize_bytes(b),
+            Reply::Bulk(None) => serializer.serialize_none(),
+            Reply::Integer(n) => Integer(*n).serialize(serializer),
+        }
+    }
+}
+
+enum AdminReply {
+    Commands(Vec<CommandInfo>),
+    Info(Vec<Option<CommandInfo>>),
+    Docs(CommandTable, Vec<String>),
+    Count(usize),
+    Empty,
+}
+
+impl Serialize for AdminReply {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            AdminReply::Commands(commands) => commands.serialize(serializer),
+            AdminReply::Info(commands) => commands.serialize(serializer),
+            AdminReply::Docs(table, names) => {
+                let names: Vec<&str> = names.iter().map(|n| &n[..]).collect();
+                table.docs(&names).serialize(serializer)
+            }
+            AdminReply::Count(n) => Integer(*n as i64).serialize(serializer),
+            AdminReply::Empty => Vec::<String>::new().serialize(serializer),
+        }
+    }
+}
+
+struct Entry {
+    value: Vec<u8>,
+    expires_at: Option<Instant>,
+}
+
+// 过期的 key 在访问时删除
+#[derive(Default, Clone)]
+struct Store(Arc<Mutex<HashMap<Vec<u8>, Entry>>>);
+
+fn live<'a>(map: &'a mut HashMap<Vec<u8>, Entry>, key: &[u8]) -> Option<&'a mut Entry> {
+    let expired = map
+        .get(key)
+        .and_then(|e| e.expires_at)
+        .is_some_and(|t| t <= Instant::now());
+    if expired {
+        map.remove(key);
+    }
+    map.get_mut(key)
+}
+
+fn not_integer() -> RedisError {
+    RedisError::new(ErrorCode::Err, "value is not an integer or out of range")
+}
+
+fn invalid_expire(command: &str) -> RedisError {
+    RedisError::new(
+        ErrorCode::Err,
+        format!("invalid expire time in '{}' command", command),
+    )
+}
+
+// 很大的过期时间不能让 Instant 溢出
+fn expire_at(ttl: Duration, command: &str) -> Result<Instant, RedisError> {
+    Instant::now()
+        .checked_add(ttl)
+        .ok_or_else(|| invalid_expire(command))
+}
+
+impl Handler<Request> for Store {
+    type Reply = Reply;
+
+    fn handle(&mut self, req: Request) -> Result<Reply, RedisError> {
+        // 处理命令时 panic 也不影响其他连接继续使用
+        let mut map = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let reply = match req {
+            Request::Ping(None) => Reply::Simple("PONG"),
+            Request::Ping(msg) => Reply::Bulk(msg),
+            Request::Echo(msg) => Reply::Bulk(Some(msg)),
+            Request::Get(key) => Reply::Bulk(live(&mut map, &key).map(|e| e.value.clone())),
+            Request::Set {
+                key,
+                value,
+                nx,
+                xx,
+                ex,
+                px,
+            } => {
+                if nx && xx || ex.is_some() && px.is_some() {
+                    return Err(RedisError::new(ErrorCode::Err, "syntax error"));
+                }
+                // 和 Redis 一样，SET 的过期时间必须是正数
+                if ex.or(px).is_some_and(|ttl| ttl <= 0) {
+                    return Err(invalid_expire("set"));
+                }
+                let expires_at = ex
+                    .map(|secs| Duration::from_secs(secs as u64))
+                    .or(px.map(|millis| Duration::from_millis(millis as u64)))
+                    .map(|ttl| expire_at(ttl, "set"))
+                    .transpose()?;
+                let exists = live(&mut map, &key).is_some();
+                if nx && exists || xx && !exists {
+                    return Ok(Reply::Bulk(None));
+                }
+                map.insert(key, Entry { value, expires_at });
+                Reply::Simple("OK")
+            }
+            Request::Del { keys } => {
+                let n = keys
+                    .iter()
+                    .filter(|key| live(&mut map, key).is_some() && map.remove(*key).is_some())
+                    .count();
+                Reply::Integer(n as i64)
+            }
+            Request::Exists { keys } => {
+                let n = keys
+                    .iter()
+                    .filter(|key| live(&mut map, key).is_some())
+                    .count();
+                Reply::Integer(n as i64)
+            }
+            Request::Incr(key) => {
+                let entry = live(&mut map, &key);
+                let n = match entry {
+                    Some(entry) => std::str::from_utf8(&entry.value)
+                        .ok()
+                        .and_then(|s| s.parse::<i64>().ok())
+                        .ok_or_else(not_integer)?,
+                    None => 0,
+                };
+                let n = n.checked_add(1).ok_or_else(|| {
+                    RedisError::new(ErrorCode::Err, "increment or decrement would overflow")
+                })?;
+                let value = n.to_string().into_bytes();
+                match live(&mut map, &key) {
+                    Some(entry) => entry.value = value,
+                    None => {
+                        map.insert(
+                            key,
+                            Entry {
+                                value,
+                                expires_at: None,
+                            },
+                        );
+                    }
+                }
+                Reply::Integer(n)
+            }
+            Request::Expire(key, seconds) => match live(&mut map, &key) {
+                Some(_) if seconds <= 0 => {
+                    map.remove(&key);
+                    Reply::Integer(1)
+                }
+                Some(entry) => {
+                    entry.expires_at =
+                        Some(expire_at(Duration::from_secs(seconds as u64), "expire")?);
+                    Reply::Integer(1)
+                }
+                None => Reply::Integer(0),
+            },
+        };
+        Ok(reply)
+    }
+}
+
+struct AdminHandler(CommandTable);
+
+impl Handler<Admin> for AdminHandler {
+    type Reply = AdminReply;
+
+    fn handle(&mut self, req: Admin) -> Result<AdminReply, RedisError> {
+        Ok(match req {
+            Admin::Command => AdminReply::Commands(self.0.commands().to_vec()),
+            Admin::CommandCount => AdminReply::Count(self.0.len()),
+            Admin::CommandInfo(names) => {
+                AdminReply::Info(names.iter().map(|name| self.0.get(name).cloned()).collect())
+            }
+            Admin::CommandDocs(names) => AdminReply::Docs(self.0.clone(), names),
+            // 没有可以配置的项目
+            Admin::ConfigGet(_) => AdminReply::Empty,
+        })
+    }
+}
+
+fn router(store: Store) -> Router {
+    let table = CommandTable::new()
+        .register_command::<Request>()
+        .register_command::<Admin>();
+    Router::new()
+        .route_command(store)
+        .route_command(AdminHandler(table))
+}
+
+fn handle_client(stream: TcpStream, store: Store) {
+    let reader = match stream.try_clone() {
+        Ok(reader) => reader,
+        Err(_) => return,
+    };
+    // 客户端断开或者发送了损坏的数据，关闭连接即可。
+    // serve 在读完已经收到的请求之后才 flush，pipeline 的回复一起写出
+    let _ = router(store).serve(reader, BufWriter::new(stream));
+}
+
+fn run(listener: TcpListener, store: Store) {
+    for stream in listener.incoming().flatten() {
+        let _ = stream.set_nodelay(true);
+        let store = store.clone();
+        thread::spawn(move || handle_client(stream, store));
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    let addr = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:6379".to_owned());
+    let listener = TcpListener::bind(&addr)?;
+    println!("listening on {}", listener.local_addr()?);
+    run(listener, Store::default());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_resp::de::Deserializer;
+    use std::io::Write;
+
+    #[test]
+    fn test_end_to_end() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || run(listener, Store::default()));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        // 一次写入整个 pipeline，回复按顺序返回
+        stream
+            .write_all(
+                b"*1\r\n$4\r\nPING\r\n\
+                *2\r\n$4\r\necho\r\n$2\r\nhi\r\n\
+                *3\r\n$3\r\nSET\r\n$3\r\nfoo\r\n$3\r\nbar\r\n\
+                *2\r\n$3\r\nGET\r\n$3\r\nfoo\r\n\
+                *3\r\n$6\r\nEXISTS\r\n$3\r\nfoo\r\n$4\r\nnone\r\n\
+                *2\r\n$4\r\nINCR\r\n$1\r\nn\r\n\
+                *2\r\n$4\r\nINCR\r\n$3\r\nfoo\r\n\
+                *5\r\n$3\r\nSET\r\n$3\r\nfoo\r\n$3\r\nbaz\r\n$2\r\nNX\r\n$2\r\nXX\r\n\
+                *2\r\n$3\r\nGET\r\n$1\r\nn\r\n\
+                *3\r\n$3\r\nDEL\r\n$1\r\nn\r\n$3\r\nfoo\r\n\
+                *3\r\n$6\r\nEXPIRE\r\n$1\r\nn\r\n$2\r\n10\r\n\
+                *1\r\n$3\r\nGET\r\n\
+                *1\r\n$4\r\nQUIT\r\n\
+                *2\r\n$7\r\nCOMMAND\r\n$5\r\nCOUNT\r\n\
+                *3\r\n$6\r\nCONFIG\r\n$3\r\nGET\r\n$4\r\nsave\r\n",
+            )
+            .unwrap();
+
+        let mut de = Deserializer::from_reader(&stream);
+        let mut next = |expected: Result<Option<Vec<u8>>, &str>| {
+            let reply = Result::<Option<Vec<u8>>, RedisError>::deserialize(&mut de).unwrap();
+            assert_eq!(
+                reply.map_err(|e| e.to_string()),
+                expected.map_err(str::to_owned)
+            );
+        };
+        let bulk = |s: &str| Ok(Some(s.as_bytes().to_vec()));
+        next(bulk("PONG"));
+        next(bulk("hi"));
+        next(bulk("OK"));
+        next(bulk("bar"));
+        next(bulk("1"));
+        next(bulk("1"));
+        next(Err("ERR value is not an integer or out of range"));
+        next(Err("ERR syntax error"));
+        next(bulk("1"));
+        next(bulk("2"));
+        next(bulk("0"));
+        next(Err("ERR wrong number of arguments for 'get' command"));
+        next(Err("ERR unknown command 'QUIT'"));
+        next(bulk("13"));
+
+        let config: Vec<String> = Vec::deserialize(&mut de).unwrap();
+        assert!(config.is_empty());
+
+        // 过期的 key 在访问时删除
+        (&stream)
+            .write_all(b"*5\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n$2\r\npx\r\n$1\r\n1\r\n")
+            .unwrap();
+        let ok: String = String::deserialize(&mut de).unwrap();
+        assert_eq!(ok, "OK");
+        thread::sleep(Duration::from_millis(10));
+        (&stream)
+            .write_all(b"*2\r\n$3\r\nGET\r\n$1\r\nk\r\n")
+            .unwrap();
+        let value: Option<Vec<u8>> = Option::deserialize(&mut de).unwrap();
+        assert_eq!(value, None);
+    }
+
+    #[test]
+    fn test_invalid_expire() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || run(listener, Store::default()));
+
+        // 超出范围或者不是正数的过期时间返回错误，之后的命令仍然正常处理
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(
+                b"*5\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n$2\r\nEX\r\n$19\r\n9223372036854775807\r\n\
+                *5\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n$2\r\nEX\r\n$1\r\n0\r\n\
+                *5\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n$2\r\nPX\r\n$2\r\n-5\r\n\
+                *3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n\
+                *3\r\n$6\r\nEXPIRE\r\n$1\r\nk\r\n$19\r\n9223372036854775807\r\n\
+                *2\r\n$3\r\nGET\r\n$1\r\nk\r\n",
+            )
+            .unwrap();
+
+        let mut de = Deserializer::from_reader(&stream);
+        let mut next = || {
+            Result::<Option<Vec<u8>>, RedisError>::deserialize(&mut de)
+                .unwrap()
+                .map_err(|e| e.to_string())
+        };
+        let bulk = |s: &str| Ok(Some(s.as_bytes().to_vec()));
+        let invalid =
+            |command: &str| Err(format!("ERR invalid expire time in '{}' command", command));
+        assert_eq!(next(), invalid("set"));
+        assert_eq!(next(), invalid("set"));
+        assert_eq!(next(), invalid("set"));
+        assert_eq!(next(), bulk("OK"));
+        assert_eq!(next(), invalid("expire"));
+        assert_eq!(next(), bulk("v"));
+    }
+}
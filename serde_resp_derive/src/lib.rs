@@ -0,0 +1,493 @@
+// #[derive(RespCommand)]，按照 Redis 命令的语法生成 serde_resp::command::RespCommand，
+// 以及使用这种语法的 Serialize 和 Deserialize，不需要再 derive serde 的 trait。
+//
+// 命令上的属性：
+//   #[resp(name = "SETEX")]            命令名，默认是大写的类型名或 variant 名，可以有多个单词
+//   #[resp(flags = "write denyoom")]   COMMAND 回复中的 flags，空格分隔
+//   #[resp(summary = "...", group = "string")]  COMMAND DOCS 中的说明
+// 字段上的属性：
+//   #[resp(key)]               参数是 key
+//   #[resp(optional)]          Option<T>，位置可选的参数
+//   #[resp(variadic)]          Vec<T>，至少一个参数，和 optional 一起使用时可以没有
+//   #[resp(flag)]              bool，只有关键字本身，比如 NX，关键字默认是大写的字段名
+//   #[resp(name = "EX")]       Option<T>，关键字后面跟着一个参数，比如 EX 10
+// 相邻的 flag 和关键字参数可以以任意顺序出现，和 SET key value [NX] [GET] [EX seconds] 一样。
+// optional 和 variadic 参数按位置读取，会取走后面的关键字，所以后面不能再有 flag 和关键字参数；
+// variadic 参数后面也不能再有 optional 或 variadic 参数
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Error, Fields, GenericArgument, Ident, LitStr,
+    PathArguments, Result, Type,
+};
+
+#[proc_macro_derive(RespCommand, attributes(resp))]
+pub fn derive_resp_command(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct CommandAttrs {
+    name: Option<String>,
+    flags: Vec<String>,
+    summary: Option<String>,
+    group: Option<String>,
+}
+
+enum ArgKind {
+    Required,
+    Optional,
+    // 是否可以没有
+    Variadic(bool),
+    Flag(String),
+    Keyword(String),
+}
+
+struct Arg {
+    name: String,
+    binding: Ident,
+    kind: ArgKind,
+    key: bool,
+    // 单个参数的类型，去掉了 Option 和 Vec
+    elem: Type,
+}
+
+// 一条命令：命令名、参数，以及怎样构造和解构这个值
+struct Spec {
+    attrs: CommandAttrs,
+    args: Vec<Arg>,
+    path: TokenStream2,
+    fields: FieldsStyle,
+}
+
+enum FieldsStyle {
+    Unit,
+    Named,
+    Unnamed,
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2> {
+    let ident = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "generic commands are not supported",
+        ));
+    }
+    let specs = match &input.data {
+        Data::Struct(data) => {
+            let mut attrs = command_attrs(&input.attrs)?;
+            attrs
+                .name
+                .get_or_insert_with(|| ident.to_string().to_uppercase());
+            vec![spec(attrs, quote!(#ident), &data.fields)?]
+        }
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|variant| {
+                let mut attrs = command_attrs(&variant.attrs)?;
+                let vident = &variant.ident;
+                attrs
+                    .name
+                    .get_or_insert_with(|| vident.to_string().to_uppercase());
+                spec(attrs, quote!(#ident::#vident), &variant.fields)
+            })
+            .collect::<Result<Vec<_>>>()?,
+        Data::Union(_) => return Err(Error::new_spanned(input, "unions are not supported")),
+    };
+
+    let writes = specs.iter().map(write_command);
+    let reads = {
+        // 多个单词的命令名先匹配，CONFIG GET 不会被 CONFIG 抢走
+        let mut sorted: Vec<&Spec> = specs.iter().collect();
+        sorted.sort_by_key(|spec| std::cmp::Reverse(name_len(spec)));
+        sorted.into_iter().map(read_command).collect::<Vec<_>>()
+    };
+    let infos = specs.iter().map(command_info);
+
+    Ok(quote! {
+        impl ::serde_resp::command::RespCommand for #ident {
+            fn write_command(&self, writer: &mut ::serde_resp::command::CommandWriter) {
+                match self {
+                    #(#writes)*
+                }
+            }
+
+            fn read_command(
+                reader: &mut ::serde_resp::command::CommandReader,
+            ) -> ::std::result::Result<Self, ::serde_resp::Error> {
+                #(#reads)*
+                ::std::result::Result::Err(reader.unknown())
+            }
+
+            fn command_infos() -> ::std::vec::Vec<::serde_resp::info::CommandInfo> {
+                ::std::vec![#(#infos),*]
+            }
+        }
+
+        impl ::serde::Serialize for #ident {
+            fn serialize<S: ::serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> ::std::result::Result<S::Ok, S::Error> {
+                ::serde_resp::command::serialize_resp_command(self, serializer)
+            }
+        }
+
+        impl<'de> ::serde::Deserialize<'de> for #ident {
+            fn deserialize<D: ::serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> ::std::result::Result<Self, D::Error> {
+                ::serde_resp::command::deserialize_resp_command(deserializer)
+            }
+        }
+    })
+}
+
+fn name_len(spec: &Spec) -> usize {
+    spec.attrs
+        .name
+        .as_deref()
+        .unwrap_or_default()
+        .split(' ')
+        .filter(|t| !t.is_empty())
+        .count()
+}
+
+fn command_attrs(attrs: &[Attribute]) -> Result<CommandAttrs> {
+    let mut parsed = CommandAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("resp")) {
+        attr.parse_nested_meta(|meta| {
+            let value = || -> Result<String> { Ok(meta.value()?.parse::<LitStr>()?.value()) };
+            if meta.path.is_ident("name") {
+                parsed.name = Some(value()?);
+            } else if meta.path.is_ident("flags") {
+                parsed.flags = value()?.split_whitespace().map(str::to_owned).collect();
+            } else if meta.path.is_ident("summary") {
+                parsed.summary = Some(value()?);
+            } else if meta.path.is_ident("group") {
+                parsed.group = Some(value()?);
+            } else {
+                return Err(meta.error("unknown resp attribute on command"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(parsed)
+}
+
+fn spec(attrs: CommandAttrs, path: TokenStream2, fields: &Fields) -> Result<Spec> {
+    let style = match fields {
+        Fields::Unit => FieldsStyle::Unit,
+        Fields::Named(_) => FieldsStyle::Named,
+        Fields::Unnamed(_) => FieldsStyle::Unnamed,
+    };
+    let mut args = vec![];
+    for (i, field) in fields.iter().enumerate() {
+        let binding = match &field.ident {
+            Some(ident) => ident.clone(),
+            None => format_ident!("__arg{}", i),
+        };
+        let mut key = false;
+        let mut flag = false;
+        let mut optional = false;
+        let mut variadic = false;
+        let mut token = None;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("resp"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("key") {
+                    key = true;
+                } else if meta.path.is_ident("flag") {
+                    flag = true;
+                } else if meta.path.is_ident("optional") {
+                    optional = true;
+                } else if meta.path.is_ident("variadic") {
+                    variadic = true;
+                } else if meta.path.is_ident("name") {
+                    token = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else {
+                    return Err(meta.error("unknown resp attribute on field"));
+                }
+                Ok(())
+            })?;
+        }
+
+        if flag && (key || optional || variadic) {
+            return Err(Error::new_spanned(
+                field,
+                "flag can't be combined with key, optional or variadic",
+            ));
+        }
+        if flag && !is_bool(&field.ty) {
+            return Err(Error::new_spanned(&field.ty, "flag must be a bool"));
+        }
+        if token.is_some() && (optional || variadic) {
+            return Err(Error::new_spanned(
+                field,
+                "keyword argument is already optional, remove optional or variadic",
+            ));
+        }
+        // 位置可选的参数按顺序读取，后面的参数不能被它取走
+        if let Some(prev) = args
+            .iter()
+            .rev()
+            .find(|arg: &&Arg| matches!(arg.kind, ArgKind::Optional | ArgKind::Variadic(_)))
+        {
+            let after_variadic = matches!(prev.kind, ArgKind::Variadic(_));
+            let swallowed = flag || token.is_some() || after_variadic && (optional || variadic);
+            if swallowed {
+                return Err(Error::new_spanned(
+                    field,
+                    format!("this argument would be taken by `{}` before it", prev.name),
+                ));
+            }
+        }
+
+        let name = match &field.ident {
+            Some(ident) => ident.to_string(),
+            None if key => "key".to_owned(),
+            None => "arg".to_owned(),
+        };
+        let (kind, elem) = if flag {
+            let token = token.unwrap_or_else(|| name.to_uppercase());
+            (ArgKind::Flag(token), field.ty.clone())
+        } else if let Some(token) = token {
+            (ArgKind::Keyword(token), inner_type(&field.ty, "Option")?)
+        } else if variadic {
+            (ArgKind::Variadic(optional), inner_type(&field.ty, "Vec")?)
+        } else if optional {
+            (ArgKind::Optional, inner_type(&field.ty, "Option")?)
+        } else {
+            (ArgKind::Required, field.ty.clone())
+        };
+        args.push(Arg {
+            name,
+            binding,
+            kind,
+            key,
+            elem,
+        });
+    }
+    Ok(Spec {
+        attrs,
+        args,
+        path,
+        fields: style,
+    })
+}
+
+fn is_bool(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.qself.is_none() && path.path.is_ident("bool"))
+}
+
+// Option<T> 或 Vec<T> 中的 T
+fn inner_type(ty: &Type, wrapper: &str) -> Result<Type> {
+    if let Type::Path(path) = ty {
+        if let Some(seg) = path.path.segments.last() {
+            if seg.ident == wrapper {
+                if let PathArguments::AngleBracketed(args) = &seg.arguments {
+                    if let Some(GenericArgument::Type(inner)) = args.args.first() {
+                        return Ok(inner.clone());
+                    }
+                }
+            }
+        }
+    }
+    Err(Error::new_spanned(
+        ty,
+        format!("expected {}<T> for this resp attribute", wrapper),
+    ))
+}
+
+fn pattern(spec: &Spec) -> TokenStream2 {
+    let path = &spec.path;
+    let bindings = spec.args.iter().map(|arg| &arg.binding);
+    match spec.fields {
+        FieldsStyle::Unit => quote!(#path),
+        FieldsStyle::Named => quote!(#path { #(#bindings),* }),
+        FieldsStyle::Unnamed => quote!(#path ( #(#bindings),* )),
+    }
+}
+
+fn write_command(spec: &Spec) -> TokenStream2 {
+    let pattern = pattern(spec);
+    let name = spec.attrs.name.as_deref().unwrap_or_default();
+    let writes = spec.args.iter().map(|arg| {
+        let binding = &arg.binding;
+        let write = if arg.key {
+            quote!(writer.key)
+        } else {
+            quote!(writer.arg)
+        };
+        match &arg.kind {
+            ArgKind::Required => quote!(#write(#binding);),
+            ArgKind::Optional => quote! {
+                if let ::std::option::Option::Some(value) = #binding {
+                    #write(value);
+                }
+            },
+            ArgKind::Variadic(_) => quote! {
+                for value in #binding.iter() {
+                    #write(value);
+                }
+            },
+            ArgKind::Flag(token) => quote! {
+                if *#binding {
+                    writer.token(#token);
+                }
+            },
+            ArgKind::Keyword(token) => quote! {
+                if let ::std::option::Option::Some(value) = #binding {
+                    writer.token(#token);
+                    #write(value);
+                }
+            },
+        }
+    });
+    quote! {
+        #pattern => {
+            writer.name(#name);
+            #(#writes)*
+        }
+    }
+}
+
+fn is_token(arg: &Arg) -> bool {
+    matches!(arg.kind, ArgKind::Flag(_) | ArgKind::Keyword(_))
+}
+
+fn read_command(spec: &Spec) -> TokenStream2 {
+    let name = spec.attrs.name.as_deref().unwrap_or_default();
+    let mut reads = vec![];
+    let mut i = 0;
+    while i < spec.args.len() {
+        let arg = &spec.args[i];
+        // 后面还需要留下的必需参数个数
+        let reserve = spec.args[i + 1..]
+            .iter()
+            .filter(|arg| matches!(arg.kind, ArgKind::Required | ArgKind::Variadic(false)))
+            .count();
+        let binding = &arg.binding;
+        match &arg.kind {
+            ArgKind::Required => reads.push(quote! {
+                let #binding = reader.required()?;
+            }),
+            ArgKind::Optional => reads.push(quote! {
+                let #binding = reader.optional(#reserve)?;
+            }),
+            ArgKind::Variadic(optional) => {
+                let min = if *optional { 0usize } else { 1 };
+                reads.push(quote! {
+                    let #binding = reader.variadic(#min, #reserve)?;
+                })
+            }
+            ArgKind::Flag(_) | ArgKind::Keyword(_) => {
+                // 相邻的关键字参数组成一组，以任意顺序读取
+                let end = spec.args[i..]
+                    .iter()
+                    .position(|arg| !is_token(arg))
+                    .map_or(spec.args.len(), |n| i + n);
+                let group = &spec.args[i..end];
+                let inits = group.iter().map(|arg| {
+                    let binding = &arg.binding;
+                    match arg.kind {
+                        ArgKind::Flag(_) => quote!(let mut #binding = false;),
+                        _ => quote!(let mut #binding = ::std::option::Option::None;),
+                    }
+                });
+                let matches = group.iter().map(|arg| {
+                    let binding = &arg.binding;
+                    match &arg.kind {
+                        ArgKind::Flag(token) => quote! {
+                            if !#binding && reader.token(#token) {
+                                #binding = true;
+                                continue;
+                            }
+                        },
+                        ArgKind::Keyword(token) => quote! {
+                            if #binding.is_none() && reader.token(#token) {
+                                #binding = ::std::option::Option::Some(reader.required()?);
+                                continue;
+                            }
+                        },
+                        _ => unreachable!(),
+                    }
+                });
+                reads.push(quote! {
+                    #(#inits)*
+                    loop {
+                        #(#matches)*
+                        break;
+                    }
+                });
+                i = end;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    let has_tokens = spec.args.iter().any(is_token);
+    let pattern = pattern(spec);
+    quote! {
+        if reader.match_name(#name) {
+            #(#reads)*
+            reader.finish(#has_tokens)?;
+            return ::std::result::Result::Ok(#pattern);
+        }
+    }
+}
+
+fn command_info(spec: &Spec) -> TokenStream2 {
+    let name = spec.attrs.name.as_deref().unwrap_or_default();
+    let args = spec.args.iter().map(|arg| {
+        let arg_name = &arg.name;
+        let elem = &arg.elem;
+        let kind = match (&arg.kind, arg.key) {
+            (ArgKind::Flag(_), _) => quote!(::serde_resp::info::ArgType::PureToken),
+            (_, true) => quote!(::serde_resp::info::ArgType::Key),
+            _ => quote!(<#elem as ::serde_resp::command::FromArg>::arg_type()),
+        };
+        let optional = matches!(
+            arg.kind,
+            ArgKind::Optional | ArgKind::Variadic(true) | ArgKind::Flag(_) | ArgKind::Keyword(_)
+        );
+        let multiple = matches!(arg.kind, ArgKind::Variadic(_));
+        let token = match &arg.kind {
+            ArgKind::Flag(token) | ArgKind::Keyword(token) => {
+                quote!(::std::option::Option::Some(#token.to_owned()))
+            }
+            _ => quote!(::std::option::Option::None),
+        };
+        quote! {
+            ::serde_resp::info::ArgInfo {
+                name: #arg_name.to_owned(),
+                kind: #kind,
+                optional: #optional,
+                multiple: #multiple,
+                token: #token,
+            }
+        }
+    });
+    let flags = &spec.attrs.flags;
+    let summary = spec.attrs.summary.iter();
+    let group = spec.attrs.group.iter();
+    quote! {
+        {
+            let mut info = ::serde_resp::info::CommandInfo::new(#name, ::std::vec![#(#args),*]);
+            info.flags = ::std::vec![#(#flags.to_owned()),*];
+            #(info.summary = #summary.to_owned();)*
+            #(info.group = #group.to_owned();)*
+            info
+        }
+    }
+}
@@ -0,0 +1,578 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt::{self, Display};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use super::command::Command;
+use super::de::Deserializer;
+use super::error::{Error, Result};
+use super::ser::to_bytes;
+
+// AOF 中的一条命令，offset 是它在文件中开始的位置
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub offset: usize,
+    pub command: Command,
+}
+
+// 依次读取 AOF 中的命令，跳过 '#' 开头的注解行。
+// 最后一条命令不完整时返回 Error::Truncated，其余的错误说明文件已经损坏，
+// 遇到错误之后停止迭代
+pub struct AofReader<R> {
+    de: Deserializer<R>,
+    // 最后一条完整命令结束的位置
+    valid: usize,
+    done: bool,
+}
+
+impl AofReader<File> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(AofReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> AofReader<R> {
+    pub fn new(reader: R) -> Self {
+        AofReader {
+            de: Deserializer::from_reader(reader),
+            valid: 0,
+            done: false,
+        }
+    }
+
+    // 出错时，这之前的内容都是完整的
+    pub fn valid_len(&self) -> usize {
+        self.valid
+    }
+
+    fn read_entry(&mut self) -> Result<Option<Entry>> {
+        let more = self.de.skip_annotations()?;
+        self.valid = self.de.bytes_offset();
+        if !more {
+            return Ok(None);
+        }
+        let command = self.de.next_frame(PhantomData)?;
+        let offset = self.valid;
+        self.valid = self.de.bytes_offset();
+        Ok(Some(Entry { offset, command }))
+    }
+}
+
+impl<R: Read> Iterator for AofReader<R> {
+    type Item = Result<Entry>;
+
+    fn next(&mut self) -> Option<Result<Entry>> {
+        if self.done {
+            return None;
+        }
+        match self.read_entry() {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                match e {
+                    Error::Eof => Some(Err(Error::Truncated(self.valid))),
+                    e => Some(Err(e)),
+                }
+            }
+        }
+    }
+}
+
+// 和 redis-check-aof --fix 一样，只修复结尾不完整的情况：把文件截断到
+// 最后一条完整命令，返回截断的位置。文件完整时返回 None，其他损坏返回错误
+pub fn fix<P: AsRef<Path>>(path: P) -> Result<Option<usize>> {
+    let path = path.as_ref();
+    for entry in AofReader::open(path)? {
+        match entry {
+            Ok(_) => {}
+            Err(Error::Truncated(offset)) => {
+                let file = OpenOptions::new().write(true).open(path)?;
+                file.set_len(offset as u64)?;
+                file.sync_all()?;
+                return Ok(Some(offset));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+// 和 Redis 的 appendfsync 配置相同
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fsync {
+    // 每条命令写入后都 fsync
+    Always,
+    // 距离上一次 fsync 超过一秒时 fsync
+    EverySec,
+    // 由操作系统决定什么时候落盘
+    No,
+}
+
+const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+// 向 AOF 追加命令
+pub struct AofWriter {
+    path: PathBuf,
+    file: File,
+    fsync: Fsync,
+    last_sync: Instant,
+    // 上一次 fsync 之后写入过数据
+    dirty: bool,
+}
+
+impl AofWriter {
+    // 文件不存在时创建，已经存在时在结尾追加
+    pub fn open<P: AsRef<Path>>(path: P, fsync: Fsync) -> Result<Self> {
+        let path = path.as_ref().to_owned();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(AofWriter {
+            path,
+            file,
+            fsync,
+            last_sync: Instant::now(),
+            dirty: false,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append<C: Serialize>(&mut self, cmd: &C) -> Result<()> {
+        self.file.write_all(&to_bytes(cmd)?)?;
+        self.dirty = true;
+        match self.fsync {
+            Fsync::Always => self.sync(),
+            Fsync::EverySec => self.tick(),
+            Fsync::No => Ok(()),
+        }
+    }
+
+    // everysec 时，距离上一次 fsync 超过一秒才 fsync。
+    // 写入之后一直没有新的命令时，需要由定时器调用，保证数据最多丢失一秒
+    pub fn tick(&mut self) -> Result<()> {
+        if self.fsync == Fsync::EverySec && self.last_sync.elapsed() >= SYNC_INTERVAL {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    pub fn sync(&mut self) -> Result<()> {
+        if self.dirty {
+            self.file.sync_data()?;
+            self.dirty = false;
+        }
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    // 用快照中的命令重写 AOF，原子地替换原来的文件，失败时原来的文件保持不变
+    pub fn rewrite<I, C>(&mut self, snapshot: I) -> Result<()>
+    where
+        I: IntoIterator<Item = C>,
+        C: Serialize,
+    {
+        replace_file(&self.path, |writer| {
+            for cmd in snapshot {
+                writer.write_all(&to_bytes(&cmd)?)?;
+            }
+            Ok(())
+        })?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.last_sync = Instant::now();
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+impl Drop for AofWriter {
+    fn drop(&mut self) {
+        if self.fsync != Fsync::No {
+            let _ = self.sync();
+        }
+    }
+}
+
+// Redis 7 multi-part AOF 中的文件种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AofFileType {
+    Base,
+    Incr,
+    // 重写之后等待删除的文件
+    History,
+}
+
+impl AofFileType {
+    fn as_str(self) -> &'static str {
+        match self {
+            AofFileType::Base => "b",
+            AofFileType::Incr => "i",
+            AofFileType::History => "h",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AofFile {
+    pub name: String,
+    pub seq: u64,
+    pub kind: AofFileType,
+}
+
+impl AofFile {
+    // aof-use-rdb-preamble yes 时，base 文件是 RDB 格式
+    pub fn is_rdb(&self) -> bool {
+        self.name.ends_with(".rdb")
+    }
+}
+
+// appendonly.aof.manifest 的内容，每行记录一个文件，比如
+// file appendonly.aof.1.base.rdb seq 1 type b
+// file appendonly.aof.1.incr.aof seq 1 type i
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub files: Vec<AofFile>,
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut files = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let bad = |msg: &str| Error::BadManifest(format!("line {}: {}", i + 1, msg));
+            let args = split_args(line).ok_or_else(|| bad("unbalanced quotes"))?;
+            if args.len() < 6 || args.len() % 2 != 0 {
+                return Err(bad("expected key value pairs"));
+            }
+            let (mut name, mut seq, mut kind) = (None, None, None);
+            for pair in args.chunks(2) {
+                match pair[0].as_str() {
+                    "file" => name = Some(pair[1].clone()),
+                    "seq" => seq = Some(pair[1].parse().map_err(|_| bad("invalid seq"))?),
+                    "type" => {
+                        kind = Some(match pair[1].as_str() {
+                            "b" => AofFileType::Base,
+                            "i" => AofFileType::Incr,
+                            "h" => AofFileType::History,
+                            _ => return Err(bad("unknown file type")),
+                        })
+                    }
+                    // 以后的版本可能增加新的字段
+                    _ => {}
+                }
+            }
+            match (name, seq, kind) {
+                (Some(name), Some(seq), Some(kind)) => files.push(AofFile { name, seq, kind }),
+                _ => return Err(bad("missing file, seq or type")),
+            }
+        }
+        Ok(Manifest { files })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Manifest::parse(&fs::read_to_string(path)?)
+    }
+
+    // 和 Redis 一样先写临时文件再 rename
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        replace_file(path.as_ref(), |writer| {
+            writer.write_all(self.to_string().as_bytes())?;
+            Ok(())
+        })
+    }
+
+    pub fn base(&self) -> Option<&AofFile> {
+        self.files.iter().find(|f| f.kind == AofFileType::Base)
+    }
+
+    pub fn incrs(&self) -> impl Iterator<Item = &AofFile> {
+        self.files.iter().filter(|f| f.kind == AofFileType::Incr)
+    }
+
+    // 至少有一个 base 或 incr 文件，最多一个 base 文件，文件名不重复且不包含路径，
+    // incr 文件的序号严格递增
+    pub fn validate(&self) -> Result<()> {
+        let bad = |msg: String| Err(Error::BadManifest(msg));
+        if self.base().is_none() && self.incrs().next().is_none() {
+            return bad("no base or incr file".to_owned());
+        }
+        let mut names = HashSet::new();
+        for file in &self.files {
+            if file.name.is_empty() || file.name.contains('/') {
+                return bad(format!("invalid file name '{}'", file.name));
+            }
+            if !names.insert(&file.name) {
+                return bad(format!("duplicate file '{}'", file.name));
+            }
+        }
+        if self
+            .files
+            .iter()
+            .filter(|f| f.kind == AofFileType::Base)
+            .count()
+            > 1
+        {
+            return bad("more than one base file".to_owned());
+        }
+        let mut last = None;
+        for file in self.incrs() {
+            if last.is_some_and(|seq| file.seq <= seq) {
+                return bad(format!("non-monotonic sequence number of '{}'", file.name));
+            }
+            last = Some(file.seq);
+        }
+        Ok(())
+    }
+}
+
+impl Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for file in &self.files {
+            f.write_str("file ")?;
+            write_arg(f, &file.name)?;
+            writeln!(f, " seq {} type {}", file.seq, file.kind.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Manifest {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Manifest::parse(s)
+    }
+}
+
+// 和 Redis 的 sdssplitargs 相同：空白分隔，双引号中支持 \n、\xHH 等转义，单引号中只支持 \'
+fn split_args(line: &str) -> Option<Vec<String>> {
+    let mut bytes = line.bytes().peekable();
+    let mut args = vec![];
+    loop {
+        while bytes.peek().is_some_and(|b| b.is_ascii_whitespace()) {
+            bytes.next();
+        }
+        let quote = match bytes.peek() {
+            None => return Some(args),
+            Some(&b) if b == b'"' || b == b'\'' => bytes.next(),
+            Some(_) => None,
+        };
+        let mut arg = vec![];
+        loop {
+            let b = match (bytes.next(), quote) {
+                (None, None) => break,
+                (None, Some(_)) => return None,
+                (Some(b), None) if b.is_ascii_whitespace() => break,
+                (Some(b), Some(q)) if b == q => {
+                    // 结束的引号后面必须是空白
+                    if bytes.peek().is_some_and(|b| !b.is_ascii_whitespace()) {
+                        return None;
+                    }
+                    break;
+                }
+                (Some(b'\\'), Some(b'"')) => match bytes.next()? {
+                    b'n' => b'\n',
+                    b'r' => b'\r',
+                    b't' => b'\t',
+                    b'b' => 8,
+                    b'a' => 7,
+                    b'x' => {
+                        let hex = [bytes.next()?, bytes.next()?];
+                        u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?
+                    }
+                    b => b,
+                },
+                (Some(b'\\'), Some(b'\'')) if bytes.peek() == Some(&b'\'') => bytes.next()?,
+                (Some(b), _) => b,
+            };
+            arg.push(b);
+        }
+        args.push(String::from_utf8(arg).ok()?);
+    }
+}
+
+// 文件名中有空白、引号或者不可打印的字符时，和 sdscatrepr 一样写成带转义的双引号字符串
+fn write_arg(f: &mut fmt::Formatter, arg: &str) -> fmt::Result {
+    let plain = |b: u8| b.is_ascii_graphic() && b != b'"' && b != b'\\';
+    if !arg.is_empty() && arg.bytes().all(plain) {
+        return f.write_str(arg);
+    }
+    f.write_str("\"")?;
+    for b in arg.bytes() {
+        match b {
+            b'"' => f.write_str("\\\"")?,
+            b'\\' => f.write_str("\\\\")?,
+            b'\n' => f.write_str("\\n")?,
+            b'\r' => f.write_str("\\r")?,
+            b'\t' => f.write_str("\\t")?,
+            7 => f.write_str("\\a")?,
+            8 => f.write_str("\\b")?,
+            b if b.is_ascii_graphic() || b == b' ' => write!(f, "{}", b as char)?,
+            b => write!(f, "\\x{:02x}", b)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+// 按照 manifest 依次读取 base 和 incr 文件中的命令，history 文件不读取。
+// RDB 格式的 base 文件无法解析，默认返回错误，见 set_skip_rdb_base。
+// 和 Redis 一样，只有最后一个文件的结尾可以不完整，其他文件不完整时说明已经损坏
+pub struct MultiPartReader {
+    dir: PathBuf,
+    files: VecDeque<AofFile>,
+    current: Option<(AofFile, AofReader<File>)>,
+    skip_rdb_base: bool,
+    done: bool,
+}
+
+impl MultiPartReader {
+    // manifest 中的文件名都相对于 dir
+    pub fn new<P: AsRef<Path>>(dir: P, manifest: &Manifest) -> Result<Self> {
+        manifest.validate()?;
+        let files = manifest
+            .base()
+            .into_iter()
+            .chain(manifest.incrs())
+            .cloned()
+            .collect();
+        Ok(MultiPartReader {
+            dir: dir.as_ref().to_owned(),
+            files,
+            current: None,
+            skip_rdb_base: false,
+            done: false,
+        })
+    }
+
+    // 跳过 RDB 格式的 base 文件，只读取 incr 文件。这时 base 中的快照不会被读取，
+    // 只适合检查或者转发之后的增量命令
+    pub fn set_skip_rdb_base(&mut self, skip: bool) {
+        self.skip_rdb_base = skip;
+    }
+
+    // 读取 dir 中唯一的 .manifest 文件，即 appenddirname 目录
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        let mut manifests = vec![];
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "manifest") {
+                manifests.push(path);
+            }
+        }
+        match &manifests[..] {
+            [path] => MultiPartReader::new(dir, &Manifest::load(path)?),
+            [] => Err(Error::BadManifest("no manifest file found".to_owned())),
+            _ => Err(Error::BadManifest("more than one manifest file".to_owned())),
+        }
+    }
+
+    // 正在读取的文件，出错时就是出错的文件
+    pub fn current_file(&self) -> Option<&AofFile> {
+        self.current.as_ref().map(|(file, _)| file)
+    }
+}
+
+impl Iterator for MultiPartReader {
+    type Item = Result<Entry>;
+
+    fn next(&mut self) -> Option<Result<Entry>> {
+        while !self.done {
+            if self.current.is_none() {
+                let file = match self.files.pop_front() {
+                    Some(file) => file,
+                    None => break,
+                };
+                if file.is_rdb() {
+                    if self.skip_rdb_base {
+                        continue;
+                    }
+                    self.done = true;
+                    let msg = format!("can't read RDB base file {}", file.name);
+                    return Some(Err(Error::Message(msg)));
+                }
+                match AofReader::open(self.dir.join(&file.name)) {
+                    Ok(reader) => self.current = Some((file, reader)),
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                }
+            }
+            match self.current.as_mut().unwrap().1.next() {
+                Some(Ok(entry)) => return Some(Ok(entry)),
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(match e {
+                        // 后面还有文件时，不完整的结尾说明文件已经损坏
+                        Error::Truncated(offset) if !self.files.is_empty() => {
+                            let name = &self.current.as_ref().unwrap().0.name;
+                            Error::Message(format!("{} is truncated at offset {}", name, offset))
+                        }
+                        e => e,
+                    }));
+                }
+                None => self.current = None,
+            }
+        }
+        self.done = true;
+        None
+    }
+}
+
+// 先写入同一目录下的临时文件并 fsync，再 rename 替换 path。
+// 任何一步失败时 path 保持不变，临时文件也会被删除
+fn replace_file<F>(path: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<()>,
+{
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(".tmp");
+    let temp = path.with_file_name(name);
+    let result = File::create(&temp)
+        .map_err(Error::from)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(&mut writer)?;
+            let file = writer.into_inner().map_err(|e| e.into_error())?;
+            file.sync_all()?;
+            Ok(())
+        })
+        .and_then(|_| {
+            fs::rename(&temp, path)?;
+            sync_dir(path)
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+// rename 之后 fsync 所在的目录，保证新的目录项已经落盘
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_: &Path) -> Result<()> {
+    Ok(())
+}
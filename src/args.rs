@@ -0,0 +1,394 @@
+use serde::ser::{self, Serialize};
+
+use super::error::{Error, Result};
+use super::key::KEY;
+
+// 把一个值展开成若干个 bulk string 参数，不写入任何数组头
+// 标量各自成为一个参数，列表、元组、结构体的每一项依次展开，
+// 而列表中连续的 u8 会合并成一个参数，所以 Vec<u8> 是一个参数，Vec<Vec<u8>> 是多个参数。
+// 空的字节序列无法和空列表区分，不产生参数
+#[derive(Default)]
+pub(crate) struct ArgsCollector {
+    pub(crate) args: Vec<Vec<u8>>,
+    // 正在收集的字节序列
+    bytes: Option<Vec<u8>>,
+    // 用 Key 标记的参数在 args 中的下标
+    pub(crate) keys: Vec<usize>,
+    // 当前所在列表的层数
+    depth: usize,
+    // 见 collect_arg_list
+    list_items: bool,
+}
+
+pub(crate) fn collect_args<T>(value: &T) -> Result<Vec<Vec<u8>>>
+where
+    T: ?Sized + Serialize,
+{
+    let mut collector = ArgsCollector::default();
+    value.serialize(&mut collector)?;
+    Ok(collector.args)
+}
+
+// 列表中的每一项都是一个参数，空的字节序列也保留。
+// $unknown 的参数列表 Vec<Vec<u8>> 用它展开，HELLO "" 3 写回时仍然是三项
+pub(crate) fn collect_arg_list<T>(value: &T) -> Result<Vec<Vec<u8>>>
+where
+    T: ?Sized + Serialize,
+{
+    let mut collector = ArgsCollector {
+        list_items: true,
+        ..Default::default()
+    };
+    value.serialize(&mut collector)?;
+    Ok(collector.args)
+}
+
+impl ArgsCollector {
+    fn push(&mut self, arg: Vec<u8>) {
+        self.flush_bytes();
+        self.args.push(arg);
+    }
+
+    fn flush_bytes(&mut self) {
+        if let Some(bytes) = self.bytes.as_mut() {
+            if !bytes.is_empty() {
+                let bytes = std::mem::take(bytes);
+                self.args.push(bytes);
+            }
+        }
+    }
+
+    // 进入一个列表，返回外层正在收集的字节序列，列表结束时还原
+    fn enter(&mut self) -> Option<Vec<u8>> {
+        self.flush_bytes();
+        self.depth += 1;
+        self.bytes.replace(vec![])
+    }
+
+    fn leave(&mut self, outer: Option<Vec<u8>>) {
+        if self.list_items && self.depth == 2 {
+            // 顶层列表中的一项，即使为空也是一个参数
+            if let Some(bytes) = self.bytes.take() {
+                self.args.push(bytes);
+            }
+        } else {
+            self.flush_bytes();
+        }
+        self.depth -= 1;
+        self.bytes = outer;
+    }
+}
+
+impl<'a> ser::Serializer for &'a mut ArgsCollector {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Args<'a>;
+    type SerializeTuple = Args<'a>;
+    type SerializeTupleStruct = Args<'a>;
+    type SerializeTupleVariant = Args<'a>;
+    type SerializeMap = Args<'a>;
+    type SerializeStruct = Args<'a>;
+    type SerializeStructVariant = Args<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.push(if v {
+            b"true".to_vec()
+        } else {
+            b"false".to_vec()
+        });
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.push(v.to_string().into_bytes());
+        Ok(())
+    }
+
+    // 列表中的 u8 当作字节
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        match self.bytes.as_mut() {
+            Some(bytes) => {
+                bytes.push(v);
+                Ok(())
+            }
+            None => self.serialize_u64(u64::from(v)),
+        }
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.push(v.to_string().into_bytes());
+        Ok(())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<()> {
+        Err(Error::Message("float is not supported".to_owned()))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<()> {
+        Err(Error::Message("float is not supported".to_owned()))
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.push(v.to_vec());
+        Ok(())
+    }
+
+    // 缺失的参数不展开
+    fn serialize_none(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        if name != KEY {
+            return value.serialize(self);
+        }
+        self.flush_bytes();
+        let start = self.args.len();
+        value.serialize(&mut *self)?;
+        self.flush_bytes();
+        self.keys.extend(start..self.args.len());
+        Ok(())
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        let outer = self.enter();
+        Ok(Args {
+            collector: self,
+            outer,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(Error::Message("map is not supported".to_owned()))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        self.serialize_seq(Some(len))
+    }
+}
+
+pub(crate) struct Args<'a> {
+    collector: &'a mut ArgsCollector,
+    outer: Option<Vec<u8>>,
+}
+
+impl ser::SerializeSeq for Args<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut *self.collector)
+    }
+
+    fn end(self) -> Result<()> {
+        self.collector.leave(self.outer);
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for Args<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for Args<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for Args<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeStruct for Args<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for Args<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeMap for Args<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, _key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Error::Message("map is not supported".to_owned()))
+    }
+
+    fn serialize_value<T>(&mut self, _value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Error::Message("map is not supported".to_owned()))
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::Serializer;
+
+// serde 把 Vec<u8>、[u8; N] 当作 u8 的列表，会序列化成一个个单字节的 bulk string。
+// 字段上标注 #[serde(with = "serde_resp::bytes")] 之后，改为 serialize_bytes，
+// 整体作为一个 bulk string。
+// 序列化接受任何 AsRef<[u8]>，反序列化接受任何可以从 Vec<u8> 转换的类型，
+// 所以 Vec<u8>、[u8; N]、bytes::Bytes 都可以使用
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: ?Sized + AsRef<[u8]>,
+    S: Serializer,
+{
+    serializer.serialize_bytes(value.as_ref())
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: TryFrom<Vec<u8>>,
+    D: Deserializer<'de>,
+{
+    let buf = deserializer.deserialize_byte_buf(ByteBufVisitor)?;
+    let len = buf.len();
+    T::try_from(buf).map_err(|_| de::Error::invalid_length(len, &ExpectedLength::<T>(PhantomData)))
+}
+
+struct ByteBufVisitor;
+
+impl<'de> Visitor<'de> for ByteBufVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("bulk string")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(v)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Vec<u8>, E> {
+        Ok(v.as_bytes().to_vec())
+    }
+
+    // 其它格式可能仍然把字节写成列表
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+        let mut buf = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(b) = seq.next_element()? {
+            buf.push(b);
+        }
+        Ok(buf)
+    }
+}
+
+// [u8; N] 长度不符时的错误提示
+struct ExpectedLength<T>(PhantomData<T>);
+
+impl<T> de::Expected for ExpectedLength<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "bytes convertible to {}",
+            std::any::type_name::<T>()
+        )
+    }
+}
@@ -0,0 +1,207 @@
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::Path;
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, SeqAccess, Visitor};
+use serde::{forward_to_deserialize_any, Serialize};
+
+use super::de::Deserializer;
+use super::error::{Error, Result};
+use super::ser::to_bytes;
+use super::writer::Writer;
+
+// 阻塞的同步客户端。Deserializer 在多次请求之间一直保留，
+// 服务端一次写回的多个回复不会因为缓冲区被丢弃而丢失
+pub struct Client<R, W> {
+    de: Deserializer<R>,
+    writer: W,
+    // 已经发送但是还没有读取回复的命令数
+    pending: usize,
+}
+
+impl Client<TcpStream, TcpStream> {
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Client::new(stream.try_clone()?, stream))
+    }
+}
+
+#[cfg(unix)]
+impl Client<UnixStream, UnixStream> {
+    pub fn connect_unix<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let stream = UnixStream::connect(path)?;
+        Ok(Client::new(stream.try_clone()?, stream))
+    }
+}
+
+impl<R, W> Client<R, W> {
+    // 还没有读取的回复数
+    pub fn pending_replies(&self) -> usize {
+        self.pending
+    }
+
+    // 连接上出现过协议错误，已经无法继续使用
+    pub fn is_broken(&self) -> bool {
+        self.de.is_broken()
+    }
+}
+
+impl<R: Read, W: Write> Client<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Client {
+            de: Deserializer::from_reader(reader),
+            writer,
+            pending: 0,
+        }
+    }
+
+    // 发送一条命令，不等待回复
+    pub fn send<C: Serialize>(&mut self, cmd: &C) -> Result<()> {
+        self.check()?;
+        let bytes = to_bytes(cmd)?;
+        // 写入失败时无法确定服务端收到了多少，也算作等待回复
+        self.pending += 1;
+        self.writer.write_all(&bytes)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    // 用 Writer 手动写入一条命令，比如最后一个参数用 write_bulk_from 从文件中读取，
+    // 不需要把整个命令放在内存中。f 出错时命令可能只写了一部分，连接无法继续使用
+    pub fn send_with<F>(&mut self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Writer<io::BufWriter<&mut W>>) -> Result<()>,
+    {
+        self.check()?;
+        self.pending += 1;
+        let mut writer = Writer::new(io::BufWriter::new(&mut self.writer));
+        let result = f(&mut writer).and_then(|_| writer.flush());
+        drop(writer);
+        if result.is_err() {
+            self.de.set_broken();
+        }
+        result
+    }
+
+    // 读取一条回复。回复是错误而 T 无法表示时，返回 Error::Redis；
+    // T 是 Result<_, RedisError> 时错误回复正常反序列化
+    pub fn recv<T: DeserializeOwned>(&mut self) -> Result<T> {
+        self.check()?;
+        let reply = self.de.next_reply(PhantomData);
+        self.pending = self.pending.saturating_sub(1);
+        reply
+    }
+
+    // 读取一条 bulk string 回复，内容直接写入 writer，返回它的长度，null 返回 None。
+    // 适合 GET 很大的值、DUMP 等回复，不需要把整个回复放在内存中
+    pub fn recv_bulk_to<O: Write>(&mut self, writer: &mut O) -> Result<Option<usize>> {
+        self.check()?;
+        let reply = self.de.next_reply_with(|de| de.read_bulk_to(writer));
+        self.pending = self.pending.saturating_sub(1);
+        reply
+    }
+
+    // 发送命令并等待它的回复
+    pub fn query<C, T>(&mut self, cmd: &C) -> Result<T>
+    where
+        C: Serialize,
+        T: DeserializeOwned,
+    {
+        self.send(cmd)?;
+        self.recv()
+    }
+
+    // 一次写入多条命令，再依次读取它们的回复
+    pub fn pipeline(&mut self) -> Pipeline<'_, R, W> {
+        Pipeline {
+            client: self,
+            buf: vec![],
+            count: 0,
+        }
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.is_broken() {
+            let e = io::Error::new(io::ErrorKind::BrokenPipe, "connection is broken");
+            return Err(Error::Io(e));
+        }
+        Ok(())
+    }
+}
+
+pub struct Pipeline<'a, R, W> {
+    client: &'a mut Client<R, W>,
+    buf: Vec<u8>,
+    count: usize,
+}
+
+impl<'a, R: Read, W: Write> Pipeline<'a, R, W> {
+    pub fn cmd<C: Serialize>(&mut self, cmd: &C) -> Result<&mut Self> {
+        self.buf.extend(to_bytes(cmd)?);
+        self.count += 1;
+        Ok(self)
+    }
+
+    // 发送所有命令，把回复依次反序列化为 T 的元素，T 可以是元组或者 Vec。
+    // 出错时剩下的回复也会被读掉，连接仍然可以继续使用
+    pub fn query<T: DeserializeOwned>(&mut self) -> Result<T> {
+        self.client.check()?;
+        let count = std::mem::take(&mut self.count);
+        self.client.pending += count;
+        self.client.writer.write_all(&self.buf)?;
+        self.client.writer.flush()?;
+        self.buf.clear();
+        let mut replies = Replies {
+            client: &mut *self.client,
+            remaining: count,
+        };
+        let result = T::deserialize(&mut replies);
+        while replies.remaining > 0 && !replies.client.de.is_broken() {
+            replies.remaining -= 1;
+            replies.client.de.skip_frame()?;
+        }
+        self.client.pending -= count;
+        result
+    }
+}
+
+// 把 pipeline 的多条回复看作一个序列
+struct Replies<'a, R, W> {
+    client: &'a mut Client<R, W>,
+    remaining: usize,
+}
+
+impl<'de, 'a, 'b, R: Read, W: Write> de::Deserializer<'de> for &'b mut Replies<'a, R, W> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de, 'a, 'b, R: Read, W: Write> SeqAccess<'de> for &'b mut Replies<'a, R, W> {
+    type Error = Error;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        self.client.de.next_reply(seed).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
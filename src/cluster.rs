@@ -0,0 +1,153 @@
+use std::fmt::{self, Display};
+
+use serde::Serialize;
+
+use super::command::Command;
+use super::de::from_reader;
+use super::error::{Error, Result};
+use super::key::get_keys;
+use super::reply::{ErrorCode, RedisError};
+use super::ser::to_bytes;
+
+// Redis Cluster 一共 16384 个 slot
+pub const SLOT_COUNT: u16 = 16384;
+
+// CRC16 XMODEM，多项式 0x1021
+const CRC16_TABLE: [u16; 256] = crc16_table();
+
+const fn crc16_table() -> [u16; 256] {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &b| {
+        (crc << 8) ^ CRC16_TABLE[usize::from((crc >> 8) as u8 ^ b)]
+    })
+}
+
+// key 所在的 slot。key 中有非空的 {hashtag} 时只计算 hashtag，
+// 这样 {user1000}.following 和 {user1000}.followers 会在同一个 slot
+pub fn key_slot(key: &[u8]) -> u16 {
+    let hashed = match key.iter().position(|&b| b == b'{') {
+        Some(open) => match key[open + 1..].iter().position(|&b| b == b'}') {
+            Some(len) if len > 0 => &key[open + 1..open + 1 + len],
+            _ => key,
+        },
+        None => key,
+    };
+    crc16(hashed) % SLOT_COUNT
+}
+
+// 命令中第一个 key 的位置，命令名的第一项是第 0 个，和 COMMAND 的 first key 一致
+// 没有 key 的命令，比如 PING，返回 None。大多数命令的 key 紧跟在命令名之后
+pub trait KeyPosition {
+    fn first_key(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+impl KeyPosition for Command {}
+
+// 计算命令应该发往的 slot，命令序列化之后按照 first_key 取出 key
+pub fn command_slot<T>(cmd: &T) -> Result<Option<u16>>
+where
+    T: Serialize + KeyPosition,
+{
+    let pos = match cmd.first_key() {
+        Some(pos) => pos,
+        None => return Ok(None),
+    };
+    let parsed: Command = from_reader(&to_bytes(cmd)?[..])?;
+    let key = match pos {
+        0 => Some(&parsed.name),
+        _ => parsed.args.get(pos - 1),
+    };
+    Ok(key.map(|key| key_slot(key)))
+}
+
+// 按照 Key 标记的参数计算 slot，没有 key 时返回 None，
+// 多个 key 不在同一个 slot 时返回 CrossSlot 错误，和 Redis Cluster 的行为一致
+pub fn keys_slot<T>(cmd: &T) -> Result<Option<u16>>
+where
+    T: ?Sized + Serialize,
+{
+    let mut slot = None;
+    for key in get_keys(cmd)? {
+        let current = key_slot(&key);
+        match slot {
+            Some(prev) if prev != current => return Err(Error::CrossSlot),
+            _ => slot = Some(current),
+        }
+    }
+    Ok(slot)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectKind {
+    // slot 已经迁移到其它节点，之后的请求都应该发往新的节点
+    Moved,
+    // slot 正在迁移，只有这一次请求需要先发送 ASKING 再发往新的节点
+    Ask,
+}
+
+// -MOVED 3999 127.0.0.1:6381 和 -ASK 3999 127.0.0.1:6381 这样的重定向错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub kind: RedirectKind,
+    pub slot: u16,
+    pub addr: String,
+}
+
+impl Redirect {
+    // 不是重定向错误，或者格式不对时返回 None
+    pub fn from_error(err: &RedisError) -> Option<Redirect> {
+        let kind = match err.code() {
+            ErrorCode::Moved => RedirectKind::Moved,
+            ErrorCode::Ask => RedirectKind::Ask,
+            _ => return None,
+        };
+        let mut parts = err.message().split(' ');
+        let slot = parts
+            .next()?
+            .parse()
+            .ok()
+            .filter(|&slot| slot < SLOT_COUNT)?;
+        let addr = parts.next().filter(|addr| !addr.is_empty())?.to_owned();
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Redirect { kind, slot, addr })
+    }
+}
+
+impl Display for Redirect {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            RedirectKind::Moved => "MOVED",
+            RedirectKind::Ask => "ASK",
+        };
+        write!(formatter, "{} {} {}", kind, self.slot, self.addr)
+    }
+}
+
+impl From<Redirect> for RedisError {
+    fn from(redirect: Redirect) -> RedisError {
+        RedisError::parse(redirect.to_string().as_bytes())
+    }
+}
@@ -0,0 +1,476 @@
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+
+use super::error::{self, Error};
+use super::info::{ArgType, CommandInfo};
+use super::key::{Key, KEY};
+use super::ser::name_tokens;
+
+// 命令名在运行时才确定的命令，比如模块命令，或者代理转发的用户输入
+// 格式和其它命令一样，是 array of bulk strings。命令名只占一项，
+// CLIENT SETNAME 这样的命令，SETNAME 是第一个参数
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Command {
+    pub name: Vec<u8>,
+    pub args: Vec<Vec<u8>>,
+}
+
+// 借用数据的 Command，序列化时不需要复制参数
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CommandRef<'a> {
+    pub name: &'a [u8],
+    pub args: Vec<&'a [u8]>,
+}
+
+// 可以作为命令参数的类型，整数按照十进制写成字符串
+pub trait ToArg {
+    fn to_arg(&self) -> Vec<u8>;
+}
+
+impl Command {
+    pub fn new<N: Into<Vec<u8>>>(name: N) -> Self {
+        Command {
+            name: name.into(),
+            args: vec![],
+        }
+    }
+
+    // 追加一个参数，可以链式调用，比如 Command::new("SET").arg("key").arg(42)
+    pub fn arg<A: ToArg>(mut self, arg: A) -> Self {
+        self.args.push(arg.to_arg());
+        self
+    }
+
+    pub fn borrowed(&self) -> CommandRef<'_> {
+        CommandRef {
+            name: &self.name,
+            args: self.args.iter().map(|arg| &arg[..]).collect(),
+        }
+    }
+}
+
+impl<'a> CommandRef<'a> {
+    pub fn new(name: &'a [u8]) -> Self {
+        CommandRef { name, args: vec![] }
+    }
+
+    pub fn arg(mut self, arg: &'a [u8]) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    pub fn into_owned(self) -> Command {
+        Command {
+            name: self.name.to_vec(),
+            args: self.args.into_iter().map(<[u8]>::to_vec).collect(),
+        }
+    }
+}
+
+impl<'a> From<CommandRef<'a>> for Command {
+    fn from(cmd: CommandRef<'a>) -> Command {
+        cmd.into_owned()
+    }
+}
+
+impl<T: ToArg + ?Sized> ToArg for &T {
+    fn to_arg(&self) -> Vec<u8> {
+        (**self).to_arg()
+    }
+}
+
+impl ToArg for [u8] {
+    fn to_arg(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+impl<const N: usize> ToArg for [u8; N] {
+    fn to_arg(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+impl ToArg for Vec<u8> {
+    fn to_arg(&self) -> Vec<u8> {
+        self.clone()
+    }
+}
+
+impl ToArg for str {
+    fn to_arg(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl ToArg for String {
+    fn to_arg(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+macro_rules! int_to_arg {
+    ($($t: ty)*) => {
+        $(
+            impl ToArg for $t {
+                fn to_arg(&self) -> Vec<u8> {
+                    self.to_string().into_bytes()
+                }
+            }
+        )*
+    };
+}
+
+int_to_arg!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+
+impl<T: ToArg> ToArg for Key<T> {
+    fn to_arg(&self) -> Vec<u8> {
+        self.0.to_arg()
+    }
+}
+
+// 从一个参数解析出的类型，arg_type 用于生成 COMMAND DOCS
+pub trait FromArg: Sized {
+    fn from_arg(arg: Vec<u8>) -> error::Result<Self>;
+
+    fn arg_type() -> ArgType {
+        ArgType::String
+    }
+}
+
+impl FromArg for Vec<u8> {
+    fn from_arg(arg: Vec<u8>) -> error::Result<Self> {
+        Ok(arg)
+    }
+}
+
+impl FromArg for String {
+    fn from_arg(arg: Vec<u8>) -> error::Result<Self> {
+        String::from_utf8(arg).map_err(|_| Error::Message("invalid utf-8 argument".to_owned()))
+    }
+}
+
+impl<T: FromArg> FromArg for Key<T> {
+    fn from_arg(arg: Vec<u8>) -> error::Result<Self> {
+        T::from_arg(arg).map(Key)
+    }
+
+    fn arg_type() -> ArgType {
+        ArgType::Key
+    }
+}
+
+macro_rules! int_from_arg {
+    ($($t: ty)*) => {
+        $(
+            impl FromArg for $t {
+                fn from_arg(arg: Vec<u8>) -> error::Result<Self> {
+                    std::str::from_utf8(&arg)
+                        .ok()
+                        .and_then(|s| s.parse().ok())
+                        .ok_or(Error::BadNumContent)
+                }
+
+                fn arg_type() -> ArgType {
+                    ArgType::Integer
+                }
+            }
+        )*
+    };
+}
+
+int_from_arg!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+
+// 有确定语法的命令，一般由 #[derive(RespCommand)] 生成，
+// 可选参数、关键字和变长参数都直接展开在命令中，比如 SET key value NX EX 10
+pub trait RespCommand: Sized {
+    fn write_command(&self, writer: &mut CommandWriter);
+
+    // 匹配命令名并读取参数，不检查多余的参数
+    fn read_command(reader: &mut CommandReader) -> error::Result<Self>;
+
+    fn command_infos() -> Vec<CommandInfo>;
+
+    fn to_command(&self) -> Command {
+        let mut writer = CommandWriter::default();
+        self.write_command(&mut writer);
+        writer.into_command()
+    }
+
+    fn from_command(cmd: Command) -> error::Result<Self> {
+        Self::read_command(&mut CommandReader::new(cmd))
+    }
+}
+
+// RespCommand 的命令名和参数依次写入这里，同时记录 key 的位置
+#[derive(Debug, Default)]
+pub struct CommandWriter {
+    args: Vec<Vec<u8>>,
+    keys: Vec<usize>,
+}
+
+impl CommandWriter {
+    pub fn name(&mut self, name: &str) {
+        for token in name_tokens(name) {
+            self.args.push(token.as_bytes().to_vec());
+        }
+    }
+
+    pub fn token(&mut self, token: &str) {
+        self.args.push(token.as_bytes().to_vec());
+    }
+
+    pub fn arg<A: ToArg + ?Sized>(&mut self, arg: &A) {
+        self.args.push(arg.to_arg());
+    }
+
+    pub fn key<A: ToArg + ?Sized>(&mut self, key: &A) {
+        self.keys.push(self.args.len());
+        self.args.push(key.to_arg());
+    }
+
+    pub fn into_command(self) -> Command {
+        let mut args = self.args.into_iter();
+        Command {
+            name: args.next().unwrap_or_default(),
+            args: args.collect(),
+        }
+    }
+}
+
+// key 写成 Key 标记过的 bulk string，get_keys 可以取出来
+impl Serialize for CommandWriter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        struct KeyBulk<'a>(&'a [u8]);
+
+        impl Serialize for KeyBulk<'_> {
+            fn serialize<S: Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                serializer.serialize_newtype_struct(KEY, &Bulk(self.0))
+            }
+        }
+
+        let mut seq = serializer.serialize_seq(Some(self.args.len()))?;
+        for (i, arg) in self.args.iter().enumerate() {
+            if self.keys.contains(&i) {
+                seq.serialize_element(&KeyBulk(arg))?;
+            } else {
+                seq.serialize_element(&Bulk(arg))?;
+            }
+        }
+        seq.end()
+    }
+}
+
+// 按照命令的语法依次读取参数，参数不够时返回 WrongArity
+#[derive(Debug)]
+pub struct CommandReader {
+    args: Vec<Vec<u8>>,
+    pos: usize,
+    // 匹配到的命令名，用于错误信息
+    name: String,
+}
+
+impl CommandReader {
+    pub fn new(cmd: Command) -> Self {
+        let mut args = Vec::with_capacity(cmd.args.len() + 1);
+        args.push(cmd.name);
+        args.extend(cmd.args);
+        CommandReader {
+            args,
+            pos: 0,
+            name: String::new(),
+        }
+    }
+
+    // 命令名不区分大小写，匹配成功后从命令名之后开始读取参数
+    pub fn match_name(&mut self, name: &str) -> bool {
+        let tokens = name_tokens(name);
+        let matched = tokens.len() <= self.args.len()
+            && tokens
+                .iter()
+                .zip(&self.args)
+                .all(|(token, arg)| token.as_bytes().eq_ignore_ascii_case(arg));
+        if matched {
+            self.pos = tokens.len();
+            self.name = name.to_owned();
+        }
+        matched
+    }
+
+    pub fn unknown(&self) -> Error {
+        Error::UnknownCommand(String::from_utf8_lossy(&self.args[0]).into_owned())
+    }
+
+    fn remaining(&self) -> usize {
+        self.args.len() - self.pos
+    }
+
+    fn next<T: FromArg>(&mut self) -> error::Result<T> {
+        let arg = std::mem::take(&mut self.args[self.pos]);
+        self.pos += 1;
+        T::from_arg(arg)
+    }
+
+    pub fn required<T: FromArg>(&mut self) -> error::Result<T> {
+        if self.remaining() == 0 {
+            return Err(Error::WrongArity(self.name.clone()));
+        }
+        self.next()
+    }
+
+    // 位置可选的参数，后面还要留下 reserve 个必需的参数
+    pub fn optional<T: FromArg>(&mut self, reserve: usize) -> error::Result<Option<T>> {
+        if self.remaining() > reserve {
+            self.next().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    // 变长参数，取走除了 reserve 之外剩下的所有参数，至少 min 个
+    pub fn variadic<T: FromArg>(&mut self, min: usize, reserve: usize) -> error::Result<Vec<T>> {
+        let count = self.remaining().saturating_sub(reserve);
+        if count < min {
+            return Err(Error::WrongArity(self.name.clone()));
+        }
+        (0..count).map(|_| self.next()).collect()
+    }
+
+    // 下一个参数是给定的关键字时，消耗它并返回 true
+    pub fn token(&mut self, token: &str) -> bool {
+        if self.remaining() > 0 && self.args[self.pos].eq_ignore_ascii_case(token.as_bytes()) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // 不应该还有剩下的参数。有关键字的命令，剩下的参数是语法错误
+    pub fn finish(&self, has_tokens: bool) -> error::Result<()> {
+        match self.remaining() {
+            0 => Ok(()),
+            _ if has_tokens => Err(Error::Syntax),
+            _ => Err(Error::WrongArity(self.name.clone())),
+        }
+    }
+}
+
+// #[derive(RespCommand)] 生成的 Serialize 和 Deserialize 使用的函数
+pub fn serialize_resp_command<T, S>(cmd: &T, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    T: RespCommand,
+    S: Serializer,
+{
+    let mut writer = CommandWriter::default();
+    cmd.write_command(&mut writer);
+    writer.serialize(serializer)
+}
+
+pub fn deserialize_resp_command<'de, T, D>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    T: RespCommand,
+    D: Deserializer<'de>,
+{
+    let cmd = Command::deserialize(deserializer)?;
+    T::from_command(cmd).map_err(error::pass_through)
+}
+
+// 命令中的每一项都用 serialize_bytes 写成 bulk string
+struct Bulk<'a>(&'a [u8]);
+
+impl Serialize for Bulk<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+fn serialize_command<S: Serializer>(
+    serializer: S,
+    name: &[u8],
+    args: &[&[u8]],
+) -> Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_seq(Some(args.len() + 1))?;
+    seq.serialize_element(&Bulk(name))?;
+    for arg in args {
+        seq.serialize_element(&Bulk(arg))?;
+    }
+    seq.end()
+}
+
+impl Serialize for Command {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let args: Vec<&[u8]> = self.args.iter().map(|arg| &arg[..]).collect();
+        serialize_command(serializer, &self.name, &args)
+    }
+}
+
+impl Serialize for CommandRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_command(serializer, self.name, &self.args)
+    }
+}
+
+// 反序列化时每一项都当作原始的字节
+struct BulkBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for BulkBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BulkBufVisitor;
+
+        impl<'de> Visitor<'de> for BulkBufVisitor {
+            type Value = BulkBuf;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("bulk string")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<BulkBuf, E> {
+                Ok(BulkBuf(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<BulkBuf, E> {
+                Ok(BulkBuf(v))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<BulkBuf, E> {
+                Ok(BulkBuf(v.as_bytes().to_vec()))
+            }
+        }
+
+        deserializer.deserialize_bytes(BulkBufVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Command {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CommandVisitor;
+
+        impl<'de> Visitor<'de> for CommandVisitor {
+            type Value = Command;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("array of bulk strings")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Command, A::Error> {
+                let name = match seq.next_element::<BulkBuf>()? {
+                    Some(name) => name.0,
+                    None => return Err(de::Error::invalid_length(0, &self)),
+                };
+                let mut args = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(arg) = seq.next_element::<BulkBuf>()? {
+                    args.push(arg.0);
+                }
+                Ok(Command { name, args })
+            }
+        }
+
+        deserializer.deserialize_seq(CommandVisitor)
+    }
+}
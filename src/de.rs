@@ -1,22 +1,35 @@
 use std::ops::{AddAssign, MulAssign, Neg};
 
 use serde::de::{
-    self, DeserializeOwned, DeserializeSeed, EnumAccess, SeqAccess, VariantAccess, Visitor,
+    self,
+    value::{BytesDeserializer, SeqDeserializer},
+    DeserializeOwned, DeserializeSeed, EnumAccess, SeqAccess, VariantAccess, Visitor,
 };
 
 use super::error::{Error, Result};
+use super::key::KEY;
+use super::raw::RAW_FRAME;
+pub use super::reader::Limits;
+use super::reader::{Header, Reader};
+use super::reply::RedisError;
+use super::ser::name_tokens;
 
-use std::io::{self, BufRead, Read};
+use std::io;
 
 use std::marker::PhantomData;
 
-const CR: u8 = b'\r';
-const LF: u8 = b'\n';
+// 捕获未知命令的 variant 名。枚举中如果有
+// #[serde(rename = "$unknown")] Unknown { name: Vec<u8>, args: Vec<Vec<u8>> }
+// 这样的 variant，匹配不到的命令会反序列化到这里，而不是报错
+pub const UNKNOWN_VARIANT: &str = "$unknown";
 
 //不存在借用数据，所以没有使用'de标记
 pub struct Deserializer<R> {
-    reader: io::BufReader<R>,
-    byte_offset: usize,
+    reader: Reader<R>,
+    // 刚刚进入 Some，还没有读取任何内容，编码方式见 ser::Serializer::wrap_some
+    in_some: bool,
+    // 出现了致命错误，或者无法跳过出错的帧，后续的数据已经无法解析
+    broken: bool,
 }
 
 pub fn from_reader<R, T>(r: R) -> Result<T>
@@ -32,6 +45,8 @@ where
 pub struct IterDerserialzier<R, T> {
     de: Deserializer<R>,
     output: PhantomData<T>,
+    // 无法跳过出错的帧时，后续的数据已经无法解析，停止迭代
+    failed: bool,
 }
 
 impl<R, T> Iterator for IterDerserialzier<R, T>
@@ -42,129 +57,208 @@ where
     type Item = Result<T>;
 
     fn next(&mut self) -> Option<Result<T>> {
-        match self.de.peek_char() {
+        if self.failed {
+            return None;
+        }
+        match self.de.peek_header() {
             Err(Error::Eof) => None,
-            Err(e) => Some(Err(e)),
-            Ok(_) => Some(T::deserialize(&mut self.de)),
+            // 内容错误的 integer 已经整行读完，可以继续读下一帧
+            Err(e) => {
+                self.failed = e.is_fatal();
+                Some(Err(e))
+            }
+            Ok(_) => {
+                // 反序列化失败时跳过这一帧剩下的部分，保证 pipeline 中后续的命令仍然对齐
+                let result = self.de.next_frame(PhantomData);
+                self.failed = self.de.is_broken();
+                Some(result)
+            }
         }
     }
 }
 
+impl<R> Deserializer<R> {
+    // 数据流是否已经无法继续解析
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+
+    // 数据流之外的原因让连接无法继续使用，比如请求只写了一部分
+    pub(crate) fn set_broken(&mut self) {
+        self.broken = true;
+    }
+}
+
 impl<R: io::Read> Deserializer<R> {
     pub fn from_reader(r: R) -> Self {
+        Deserializer::new(Reader::new(r))
+    }
+
+    // 在已有的 Reader 上反序列化，比如先用事件读取外层的数组，再逐个反序列化其中的元素
+    pub fn new(reader: Reader<R>) -> Self {
         Deserializer {
-            reader: io::BufReader::new(r),
-            byte_offset: 0,
+            reader,
+            in_some: false,
+            broken: false,
         }
     }
 
+    pub fn reader_mut(&mut self) -> &mut Reader<R> {
+        &mut self.reader
+    }
+
+    pub fn into_reader(self) -> Reader<R> {
+        self.reader
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.reader.set_limits(limits);
+    }
+
+    // 把下一个 bulk string 的内容直接写入 writer，见 Reader::read_bulk_to
+    pub fn read_bulk_to<W: io::Write>(&mut self, writer: &mut W) -> Result<Option<usize>> {
+        self.in_some = false;
+        self.reader.read_bulk_to(writer)
+    }
+
     pub fn bytes_offset(&self) -> usize {
-        self.byte_offset
+        self.reader.bytes_offset()
     }
 
-    pub fn into_iter<T>(self) -> IterDerserialzier<R, T> {
-        IterDerserialzier {
-            de: self,
-            output: PhantomData,
-        }
+    // 见 Reader::has_buffered
+    pub fn has_buffered(&self) -> bool {
+        self.reader.has_buffered()
     }
 
-    // parser
-    // 查看第一个u8
-    fn peek_char(&mut self) -> Result<u8> {
-        Ok(self.peek_nchar(1)?[0])
+    // 反序列化下一帧。失败时跳过这一帧剩下的部分，保证后续的帧仍然对齐，
+    // 无法跳过时标记为 broken
+    pub(crate) fn next_frame<'de, S>(&mut self, seed: S) -> Result<S::Value>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        self.next_frame_with(|de| seed.deserialize(de))
     }
 
-    fn peek_nchar(&mut self, n: usize) -> Result<&[u8]> {
-        while self.reader.buffer().len() < n {
-            if self.reader.fill_buf()?.len() == 0 {
-                return Err(Error::Eof);
+    // 同 next_frame，由 f 读取这一帧
+    pub(crate) fn next_frame_with<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        let frames = self.reader.frames();
+        let result = f(self);
+        if let Err(e) = &result {
+            if e.is_fatal() || self.reader.skip_frame_rest(frames).is_err() {
+                self.broken = true;
             }
         }
-        Ok(&self.reader.buffer()[0..n])
+        result
     }
 
-    fn consume(&mut self, n: usize) {
-        self.reader.consume(n)
+    // 跳过以 '#' 开头的注释行，例如 Redis 7 AOF 中的时间戳注解。
+    // 只能在两帧之间调用，返回 false 表示数据已经读完
+    pub(crate) fn skip_annotations(&mut self) -> Result<bool> {
+        self.reader.skip_annotations()
     }
 
-    fn next_char(&mut self) -> Result<u8> {
-        let ch = self.peek_char()?;
-        self.consume(1);
-        self.byte_offset += 1;
-        Ok(ch)
+    // 作为回复反序列化下一帧。回复是错误而目标类型无法表示时，返回 Error::Redis
+    pub(crate) fn next_reply<'de, S>(&mut self, seed: S) -> Result<S::Value>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        self.next_reply_with(|de| seed.deserialize(de))
     }
-    // TODO: 不要使用Vec<u8>，直接返回内部buffer的引用
-    fn next_lf(&mut self) -> Result<Vec<u8>> {
-        let mut buf = vec![];
-        let n = self.reader.read_until(LF, &mut buf)?;
-        if n == 0 {
-            return Err(Error::Eof);
-        }
 
-        if n < 2 || buf[n - 2] != CR {
-            return Err(Error::UnbalancedCRLF);
+    // 同 next_reply，由 f 读取这一帧
+    pub(crate) fn next_reply_with<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        let error = self.peek_error()?;
+        match self.next_frame_with(f) {
+            Err(_) if error.is_some() && !self.broken => Err(Error::Redis(error.unwrap())),
+            result => result,
         }
-
-        Ok(buf)
     }
 
-    fn next_length_hint(&mut self) -> Result<Option<usize>> {
-        let buf = self.next_lf()?;
-        let n = buf.len();
-        if buf[0] == b'-' {
-            if buf.len() == 4 || buf == b"-1\r\n" {
-                return Ok(None);
-            } else {
-                return Err(Error::BadLengthHint);
-            }
-        }
-        let mut len = 0;
-        for &ch in buf.iter().take(n - 2) {
-            match ch {
-                ch @ b'0'..=b'9' => {
-                    len *= 10;
-                    len += usize::from(ch as u8 - b'0');
+    // 下一帧是错误回复时返回它，不消耗这一帧
+    fn peek_error(&mut self) -> Result<Option<RedisError>> {
+        match self.peek_header() {
+            Ok(Header::Error(line)) => Ok(Some(RedisError::parse(line))),
+            Ok(_) => Ok(None),
+            Err(e) => {
+                if e.is_fatal() {
+                    self.broken = true;
                 }
-                _ => return Err(Error::BadLengthHint),
+                Err(e)
             }
         }
-        self.byte_offset += n;
-        Ok(Some(len))
     }
 
+    // 跳过完整的一帧
+    pub(crate) fn skip_frame(&mut self) -> Result<()> {
+        let result = self.skip_element();
+        if result.is_err() {
+            self.broken = true;
+        }
+        result
+    }
+
+    pub fn into_iter<T>(self) -> IterDerserialzier<R, T> {
+        IterDerserialzier {
+            de: self,
+            output: PhantomData,
+            failed: false,
+        }
+    }
+
+    // 以下都交给 Reader，进入下一个元素时 in_some 结束
+    fn peek_header(&mut self) -> Result<&Header> {
+        self.reader.peek_header()
+    }
+
+    fn next_header(&mut self) -> Result<Header> {
+        self.in_some = false;
+        self.reader.next_header()
+    }
+
+    fn read_bulk_data(&mut self, len: usize) -> Result<Vec<u8>> {
+        self.reader.read_bulk_data(len)
+    }
+
+    fn skip_element(&mut self) -> Result<()> {
+        self.in_some = false;
+        self.reader.skip_element()
+    }
+
+    fn read_raw(&mut self) -> Result<Vec<u8>> {
+        self.in_some = false;
+        self.reader.read_raw()
+    }
+
+    // 服务端的回复中，simple string 和 integer 也当作字符串的内容
     fn parse_bulk_string(&mut self) -> Result<Option<Vec<u8>>> {
-        if self.next_char()? != b'$' {
-            return Err(Error::ExpectedDollarSign);
-        }
-        match self.next_length_hint()? {
-            Some(len) => {
-                let mut buf = vec![0; len + 2];
-                self.reader.read_exact(&mut buf)?;
-                if buf[len + 1] != LF {
-                    return Err(Error::ExpectedLF);
-                }
-                if buf[len] != CR {
-                    return Err(Error::UnbalancedCRLF);
-                }
-                self.byte_offset += len + 2;
-                buf.pop(); // pop lf
-                buf.pop(); // pop cr
-                Ok(Some(buf))
-            }
-            None => Ok(None),
+        match self.peek_header()? {
+            Header::Bulk(_) | Header::Simple(_) | Header::Integer(_) => {}
+            _ => return Err(Error::ExpectedDollarSign),
+        }
+        match self.next_header()? {
+            Header::Bulk(Some(len)) => self.read_bulk_data(len).map(Some),
+            Header::Simple(line) | Header::Integer(line) => Ok(Some(line)),
+            _ => Ok(None),
         }
     }
 
+    // 不接受 null bulk string
+    fn parse_bulk(&mut self) -> Result<Vec<u8>> {
+        self.parse_bulk_string()?
+            .ok_or(Error::ExpectedMoreBulkString)
+    }
+
     fn parse_bool(&mut self) -> Result<bool> {
-        if self.peek_nchar(10)? == b"$4\r\ntrue\r\n" {
-            self.consume(10);
-            Ok(true)
-        } else if self.peek_nchar(11)? == b"$5\r\nfalse\r\n" {
-            self.consume(11);
-            Ok(false)
-        } else {
-            Err(Error::ExpectedBoolean)
+        match &self.parse_bulk()?[..] {
+            b"true" => Ok(true),
+            b"false" => Ok(false),
+            _ => Err(Error::ExpectedBoolean),
         }
     }
 
@@ -217,6 +311,37 @@ impl<R: io::Read> Deserializer<R> {
             None => Err(Error::BadNumContent),
         }
     }
+
+    // 读取数组头，返回数组长度
+    fn parse_array_len(&mut self) -> Result<Option<usize>> {
+        match self.peek_header()? {
+            Header::Array(_) => {}
+            _ => return Err(Error::ExpectedStarSign),
+        }
+        match self.next_header()? {
+            Header::Array(len) => Ok(len),
+            _ => unreachable!(),
+        }
+    }
+
+    // 紧挨着外层 Some 的 Option 和 unit，外层 Some 写成了只有一项的数组，先去掉它
+    fn unwrap_some(&mut self) -> Result<()> {
+        if self.in_some && self.parse_array_len()? != Some(1) {
+            return Err(Error::MismatchedLengthHint);
+        }
+        Ok(())
+    }
+
+    // 命令名可能由多个 bulk string 组成，逐个匹配，和 Redis 一样不区分大小写
+    fn match_name(&mut self, name: &str) -> Result<()> {
+        for token in name_tokens(name) {
+            match self.parse_bulk_string()? {
+                Some(parsed) if parsed.eq_ignore_ascii_case(token.as_bytes()) => {}
+                _ => return Err(Error::MismatchedName),
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<'de, 'a, R: io::Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
@@ -229,11 +354,13 @@ impl<'de, 'a, R: io::Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
     {
         unimplemented!()
     }
+    // 忽略的值不需要知道类型，直接跳过整个元素
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_any(visitor)
+        self.skip_element()?;
+        visitor.visit_unit()
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
@@ -319,7 +446,7 @@ impl<'de, 'a, R: io::Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        let s = self.parse_bulk_string()?.unwrap();
+        let s = self.parse_bulk()?;
         match std::str::from_utf8(&s) {
             Err(_) => Err(Error::ExpectedChar),
             Ok(s) => {
@@ -351,26 +478,32 @@ impl<'de, 'a, R: io::Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        let s = self.parse_bulk_string()?.unwrap();
+        let s = self.parse_bulk()?;
         visitor.visit_bytes(&s[..])
     }
 
-    fn deserialize_byte_buf<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        visitor.visit_byte_buf(self.parse_bulk()?)
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        if self.peek_nchar(5)? == b"$-1\r\n" {
-            self.consume(5);
-            visitor.visit_none()
-        } else {
-            visitor.visit_some(self)
+        self.unwrap_some()?;
+        // null bulk string 和 null array 都表示 None
+        match self.peek_header()? {
+            Header::Bulk(None) | Header::Array(None) => {
+                self.next_header()?;
+                visitor.visit_none()
+            }
+            _ => {
+                self.in_some = true;
+                visitor.visit_some(self)
+            }
         }
     }
 
@@ -378,6 +511,7 @@ impl<'de, 'a, R: io::Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
+        self.unwrap_some()?;
         match self.parse_bulk_string()? {
             Some(_) => Err(Error::ExpectedNone),
             None => visitor.visit_unit(),
@@ -389,63 +523,50 @@ impl<'de, 'a, R: io::Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        if self.next_char()? != b'*' {
-            return Err(Error::ExpectedStarSign);
-        }
-        if let Some(1) = self.next_length_hint()? {
-            match self.parse_bulk_string()? {
-                Some(parsed_name) => {
-                    if parsed_name == name.as_bytes() {
-                        // 检查完成，提示 visitor 可以直接构建 unit struct
-                        visitor.visit_unit()
-                    } else {
-                        Err(Error::MismatchedName)
-                    }
-                }
-                None => Err(Error::MismatchedName),
-            }
-        } else {
-            Err(Error::BadLengthHint)
+        if self.parse_array_len()? != Some(name_tokens(name).len()) {
+            return Err(Error::WrongArity(name.to_owned()));
         }
+        self.match_name(name)?;
+        // 检查完成，提示 visitor 可以直接构建 unit struct
+        visitor.visit_unit()
     }
 
     fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        if self.next_char()? != b'*' {
-            return Err(Error::ExpectedStarSign);
+        if name == KEY {
+            return visitor.visit_newtype_struct(self);
         }
-        if let Some(2) = self.next_length_hint()? {
-            match self.parse_bulk_string()? {
-                Some(parsed_name) => {
-                    if parsed_name == name.as_bytes() {
-                        // 检查完成，visitor 继续构建 newtype
-                        visitor.visit_newtype_struct(self)
-                    } else {
-                        Err(Error::MismatchedName)
-                    }
-                }
-                None => Err(Error::MismatchedName),
-            }
-        } else {
-            return Err(Error::BadLengthHint);
+        // 原样读出下一个元素，包括 null 和错误回复
+        if name == RAW_FRAME {
+            return visitor.visit_byte_buf(self.read_raw()?);
         }
+        if self.parse_array_len()? != Some(name_tokens(name).len() + 1) {
+            return Err(Error::WrongArity(name.to_owned()));
+        }
+        self.match_name(name)?;
+        // 检查完成，visitor 继续构建 newtype
+        visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_seq<V>(mut self, visitor: V) -> Result<V::Value>
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        if self.next_char()? == b'*' {
-            if let Some(len) = self.next_length_hint()? {
-                visitor.visit_seq(BulkStrings::new(&mut self, len as u64))
-            } else {
-                // null 值已有 null bulk string, 这里默认失败
-                Err(Error::ExpectedArray)
-            }
+        // bulk string 当作字节序列，这样 Vec<u8> 这样的类型也可以从 bulk string 构建，
+        // 回复中的 simple string 和 integer 同理
+        if let Header::Bulk(Some(_)) | Header::Simple(_) | Header::Integer(_) =
+            self.peek_header()?
+        {
+            let bytes = self.parse_bulk()?;
+            return visitor.visit_seq(SeqDeserializer::new(bytes.into_iter()));
+        }
+        if let Some(len) = self.parse_array_len()? {
+            visitor.visit_seq(BulkStrings::new(self, len as u64))
         } else {
-            Err(Error::ExpectedStarSign)
+            // null 值已有 null bulk string, 这里默认失败
+            Err(Error::ExpectedArray)
         }
     }
 
@@ -458,7 +579,7 @@ impl<'de, 'a, R: io::Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
 
     // Tuple structs 消耗第一项来检查name，然后和 seq 解析相同
     fn deserialize_tuple_struct<V>(
-        mut self,
+        self,
         name: &'static str,
         len: usize,
         visitor: V,
@@ -466,29 +587,17 @@ impl<'de, 'a, R: io::Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        if self.next_char()? != b'*' {
-            return Err(Error::ExpectedStarSign);
-        }
-
-        if let Some(parsed_len) = self.next_length_hint()? {
-            if parsed_len != len + 1 {
-                return Err(Error::MismatchedLengthHint);
+        // null 值已有 null bulk string, 这里默认失败
+        match self.parse_array_len()? {
+            None => return Err(Error::MismatchedLengthHint),
+            Some(parsed) if parsed != name_tokens(name).len() + len => {
+                return Err(Error::WrongArity(name.to_owned()))
             }
-            match self.parse_bulk_string()? {
-                Some(parsed_name) => {
-                    if parsed_name == name.as_bytes() {
-                        // 检查完成，visitor 继续构建 newtype
-                        visitor.visit_seq(BulkStrings::new(&mut self, len as u64))
-                    } else {
-                        Err(Error::MismatchedName)
-                    }
-                }
-                None => Err(Error::MismatchedName),
-            }
-        } else {
-            // null 值已有 null bulk string, 这里默认失败
-            Err(Error::MismatchedLengthHint)
+            _ => {}
         }
+        self.match_name(name)?;
+        // 检查完成，visitor 继续构建 seq
+        visitor.visit_seq(BulkStrings::new(self, len as u64))
     }
 
     // resp 的反序列化暂时都可以通过 visit_seq 实现
@@ -522,22 +631,33 @@ impl<'de, 'a, R: io::Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
     }
 
     fn deserialize_enum<V>(
-        mut self,
-        _name: &'static str,
-        _variants: &'static [&'static str],
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        // Result<T, E> 是服务端的回复，错误回复得到 Err，其它的交给 T
+        if name == "Result" && variants == ["Ok", "Err"] {
+            let access = match self.peek_header()? {
+                Header::Error(_) => match self.next_header()? {
+                    Header::Error(line) => ReplyAccess::Err(line),
+                    _ => unreachable!(),
+                },
+                _ => ReplyAccess::Ok(self),
+            };
+            return visitor.visit_enum(access);
+        }
         // enum 体现为一个 array of bulk string, 不用检查name匹配，
         // 到内部 variant 反序列化时处理
-        if self.next_char()? != b'*' {
-            return Err(Error::ExpectedStarSign);
-        }
-
-        if let Some(len) = self.next_length_hint()? {
-            visitor.visit_enum(BulkStrings::new(&mut self, len as u64))
+        let top_level = self.reader.depth() == 0;
+        if let Some(len) = self.parse_array_len()? {
+            let mut access = BulkStrings::new(self, len as u64);
+            access.variants = variants;
+            access.top_level = top_level;
+            visitor.visit_enum(access)
         } else {
             // null 值已有 null bulk string, 这里默认失败
             Err(Error::MismatchedLengthHint)
@@ -545,14 +665,98 @@ impl<'de, 'a, R: io::Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
     }
 }
 
+enum ReplyAccess<'a, R> {
+    Ok(&'a mut Deserializer<R>),
+    // 去掉 - 和 CRLF 的错误回复
+    Err(Vec<u8>),
+}
+
+impl<'a, 'de, R: io::Read> EnumAccess<'de> for ReplyAccess<'a, R> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant: &[u8] = match self {
+            ReplyAccess::Ok(_) => b"Ok",
+            ReplyAccess::Err(_) => b"Err",
+        };
+        let val = seed.deserialize(BytesDeserializer::<Error>::new(variant))?;
+        Ok((val, self))
+    }
+}
+
+impl<'a, 'de, R: io::Read> VariantAccess<'de> for ReplyAccess<'a, R> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Err(Error::ExpectedDollarSign)
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self {
+            ReplyAccess::Ok(de) => seed.deserialize(de),
+            ReplyAccess::Err(line) => seed.deserialize(OwnedBulk(line)),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::ExpectedDollarSign)
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::ExpectedDollarSign)
+    }
+}
+
 struct BulkStrings<'a, R> {
     de: &'a mut Deserializer<R>,
     cnt: u64,
+    // 作为枚举访问时，记录所有 variant 的名字，用于匹配多个部分组成的命令名
+    variants: &'static [&'static str],
+    // 命令没有匹配的 variant，交给 $unknown 处理时，记录已经读到的各个 bulk string，
+    // 第一项是命令名，其余的是贪婪匹配多个部分的命令名时多读的参数
+    unknown: Option<Vec<Vec<u8>>>,
+    // 匹配到的 variant 名，参数个数不符时用于错误信息
+    variant: String,
+    // 是否是最外层的命令，而不是命令参数中的枚举
+    top_level: bool,
 }
 
 impl<'a, R> BulkStrings<'a, R> {
     fn new(de: &'a mut Deserializer<R>, cnt: u64) -> Self {
-        BulkStrings { de, cnt }
+        BulkStrings {
+            de,
+            cnt,
+            variants: &[],
+            unknown: None,
+            variant: String::new(),
+            top_level: false,
+        }
+    }
+
+    // 是否有 variant 的名字以 name 加空格开头，即 name 只是命令名的前几个部分
+    fn is_name_prefix(&self, name: &[u8]) -> bool {
+        self.variants.iter().any(|v| {
+            let tokens = name_tokens(v);
+            let parsed: Vec<&[u8]> = name.split(|&b| b == b' ').collect();
+            tokens.len() > parsed.len()
+                && tokens
+                    .iter()
+                    .zip(parsed)
+                    .all(|(t, p)| t.as_bytes().eq_ignore_ascii_case(p))
+        })
     }
 }
 
@@ -581,13 +785,82 @@ impl<'a, 'de, R: io::Read> EnumAccess<'de> for BulkStrings<'a, R> {
     where
         V: DeserializeSeed<'de>,
     {
-        let val = seed.deserialize(&mut *self.de)?;
-        self.cnt -= 1;
-        if self.cnt > 0 && self.de.peek_char()? != b'$' {
+        if self.cnt == 0 {
             return Err(Error::ExpectedMoreBulkString);
+        }
+        let first = self.de.parse_bulk_string()?.ok_or(Error::MismatchedName)?;
+        self.cnt -= 1;
+        let mut name = first.clone();
+        let mut tokens = vec![first];
+        // 命令名由多个部分组成时继续读取，比如 CLIENT 之后的 SETNAME。
+        // 这里是贪婪匹配，不要同时定义 CLIENT 和 CLIENT SETNAME 这样的 variant
+        while self.cnt > 0 && self.is_name_prefix(&name) {
+            let token = self.de.parse_bulk_string()?.ok_or(Error::MismatchedName)?;
+            self.cnt -= 1;
+            name.push(b' ');
+            name.extend_from_slice(&token);
+            tokens.push(token);
+        }
+        // 命令名不区分大小写，交给 visitor 的是 variant 本来的名字
+        let known = self
+            .variants
+            .iter()
+            .find(|v| v.as_bytes().eq_ignore_ascii_case(&name));
+        let known = match known {
+            Some(variant) => {
+                name = variant.as_bytes().to_vec();
+                true
+            }
+            None => false,
+        };
+        if !known && self.variants.contains(&UNKNOWN_VARIANT) {
+            let val =
+                seed.deserialize(BytesDeserializer::<Error>::new(UNKNOWN_VARIANT.as_bytes()))?;
+            self.unknown = Some(tokens);
+            return Ok((val, self));
+        }
+        if !known && self.top_level {
+            return Err(Error::UnknownCommand(
+                String::from_utf8_lossy(&name).into_owned(),
+            ));
+        }
+        let val = seed.deserialize(BytesDeserializer::<Error>::new(&name))?;
+        self.variant = String::from_utf8_lossy(&name).into_owned();
+        Ok((val, self))
+    }
+}
+
+impl<'a, R: io::Read> BulkStrings<'a, R> {
+    fn check_arity(&self, len: usize) -> Result<()> {
+        if self.cnt as usize == len {
+            Ok(())
         } else {
-            Ok((val, self))
+            Err(Error::WrongArity(self.variant.clone()))
+        }
+    }
+
+    // 跳过还没有读取的参数
+    fn skip_rest(&mut self) -> Result<()> {
+        while self.cnt > 0 {
+            self.cnt -= 1;
+            self.de.skip_element()?;
         }
+        Ok(())
+    }
+
+    // $unknown 按照 (命令名, 参数列表) 的顺序访问
+    fn visit_unknown<'de, V>(mut self, tokens: Vec<Vec<u8>>, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let mut tokens = tokens.into_iter();
+        let val = visitor.visit_seq(UnknownCommand {
+            name: tokens.next(),
+            front: tokens,
+            args: Some(&mut self),
+        })?;
+        self.skip_rest()?;
+        Ok(val)
     }
 }
 
@@ -595,32 +868,157 @@ impl<'a, 'de, R: io::Read> EnumAccess<'de> for BulkStrings<'a, R> {
 impl<'a, 'de, R: io::Read> VariantAccess<'de> for BulkStrings<'a, R> {
     type Error = Error;
 
-    fn unit_variant(self) -> Result<()> {
-        if self.cnt == 0 {
-            Ok(())
-        } else {
-            Err(Error::ExpectedDollarSign)
+    fn unit_variant(mut self) -> Result<()> {
+        if self.unknown.is_some() {
+            return self.skip_rest();
         }
+        self.check_arity(0)
     }
 
-    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    // newtype 形式的 $unknown 得到包括命令名在内的所有参数
+    fn newtype_variant_seed<T>(mut self, seed: T) -> Result<T::Value>
     where
         T: DeserializeSeed<'de>,
     {
-        seed.deserialize(self.de)
+        match self.unknown.take() {
+            Some(tokens) => {
+                let val = seed.deserialize(UnknownArgs {
+                    front: tokens.into_iter(),
+                    args: &mut self,
+                })?;
+                self.skip_rest()?;
+                Ok(val)
+            }
+            None => {
+                self.check_arity(1)?;
+                seed.deserialize(self.de)
+            }
+        }
     }
 
-    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    fn tuple_variant<V>(mut self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_seq(self)
+        match self.unknown.take() {
+            Some(tokens) => self.visit_unknown(tokens, visitor),
+            None => {
+                self.check_arity(len)?;
+                visitor.visit_seq(self)
+            }
+        }
     }
 
-    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    fn struct_variant<V>(mut self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.unknown.take() {
+            Some(tokens) => self.visit_unknown(tokens, visitor),
+            None => {
+                self.check_arity(fields.len())?;
+                visitor.visit_seq(self)
+            }
+        }
+    }
+}
+
+// $unknown 的 tuple 和 struct 形式，第一项是命令名，第二项是剩下的参数
+struct UnknownCommand<'a, 'b, R> {
+    name: Option<Vec<u8>>,
+    // 已经读出的参数
+    front: std::vec::IntoIter<Vec<u8>>,
+    args: Option<&'b mut BulkStrings<'a, R>>,
+}
+
+impl<'a, 'b, 'de, R: io::Read> SeqAccess<'de> for UnknownCommand<'a, 'b, R> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if let Some(name) = self.name.take() {
+            return seed.deserialize(OwnedBulk(name)).map(Some);
+        }
+        match self.args.take() {
+            Some(args) => {
+                let front = std::mem::take(&mut self.front);
+                seed.deserialize(UnknownArgs { front, args }).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+// 把还没有读取的参数当作一个列表，已经读出的 front 放在列表的最前面
+struct UnknownArgs<'a, 'b, R> {
+    front: std::vec::IntoIter<Vec<u8>>,
+    args: &'b mut BulkStrings<'a, R>,
+}
+
+impl<'a, 'b, 'de, R: io::Read> de::Deserializer<'de> for UnknownArgs<'a, 'b, R> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
         visitor.visit_seq(self)
     }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'a, 'b, 'de, R: io::Read> SeqAccess<'de> for UnknownArgs<'a, 'b, R> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.front.next() {
+            Some(arg) => seed.deserialize(OwnedBulk(arg)).map(Some),
+            None => self.args.next_element_seed(seed),
+        }
+    }
+}
+
+// 已经从数据流中读取出来的 bulk string
+struct OwnedBulk(Vec<u8>);
+
+impl<'de> de::Deserializer<'de> for OwnedBulk {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_byte_buf(self.0)
+    }
+
+    // 和 Deserializer 一致，bulk string 可以当作字节序列
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(SeqDeserializer::new(self.0.into_iter()))
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
 }
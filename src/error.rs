@@ -4,6 +4,8 @@ use std::io;
 
 use serde::{de, ser};
 
+use super::reply::{ErrorCode, RedisError};
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
@@ -25,6 +27,23 @@ pub enum Error {
     UnbalancedCRLF,
     ExpectedLF,
     TrailingBytes,
+    UnknownFrameType,
+    // 命令名没有匹配的类型
+    UnknownCommand(String),
+    // 命令的参数个数不符，记录命令名
+    WrongArity(String),
+    // 命令中的 key 不在同一个 slot
+    CrossSlot,
+    // 参数个数正确，但是关键字不能识别
+    Syntax,
+    // 服务端返回的错误回复
+    Redis(RedisError),
+    // 最后一帧不完整，记录它开始的位置
+    Truncated(usize),
+    // AOF manifest 格式错误，或者其中的文件信息不一致
+    BadManifest(String),
+    // 长度、嵌套层数等超过了 reader::Limits 的限制
+    LimitExceeded(&'static str),
 }
 
 impl ser::Error for Error {
@@ -33,9 +52,43 @@ impl ser::Error for Error {
     }
 }
 
+// 命令解析的错误按照固定的格式写成信息，见 pass_through
 impl de::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
-        Error::Message(msg.to_string())
+        let msg = msg.to_string();
+        if msg == "syntax error" {
+            return Error::Syntax;
+        }
+        if msg == "bad number content" {
+            return Error::BadNumContent;
+        }
+        if let Some(name) = msg
+            .strip_prefix("unknown command '")
+            .and_then(|rest| rest.strip_suffix('\''))
+        {
+            return Error::UnknownCommand(name.to_owned());
+        }
+        if let Some(name) = msg
+            .strip_prefix("wrong number of arguments for '")
+            .and_then(|rest| rest.strip_suffix("' command"))
+        {
+            return Error::WrongArity(name.to_owned());
+        }
+        Error::Message(msg)
+    }
+}
+
+// 在 Deserialize 的实现中返回这个 crate 的错误。错误写成固定格式的信息，
+// 这个 crate 的 custom 再从信息还原出原来的错误，比如 Error::WrongArity；
+// 其他反序列化器得到同样的错误信息
+pub(crate) fn pass_through<E: de::Error>(err: Error) -> E {
+    match err {
+        // Display 把命令名转成小写，这里保留原样
+        Error::WrongArity(name) => E::custom(format_args!(
+            "wrong number of arguments for '{}' command",
+            name
+        )),
+        err => E::custom(err),
     }
 }
 
@@ -59,10 +112,57 @@ impl Display for Error {
             Error::BadNumContent => write!(formatter, "bad number content"),
             Error::UnbalancedCRLF => write!(formatter, "unbalanced CRLF"),
             Error::TrailingBytes => write!(formatter, "trailing bytes"),
+            Error::UnknownFrameType => write!(formatter, "unknown frame type"),
+            Error::UnknownCommand(ref name) => write!(formatter, "unknown command '{}'", name),
+            Error::WrongArity(ref name) => write!(
+                formatter,
+                "wrong number of arguments for '{}' command",
+                name.to_lowercase()
+            ),
+            Error::Syntax => write!(formatter, "syntax error"),
+            Error::Redis(ref e) => Display::fmt(e, formatter),
+            Error::Truncated(offset) => write!(formatter, "truncated frame at offset {}", offset),
+            Error::BadManifest(ref msg) => write!(formatter, "invalid AOF manifest: {}", msg),
+            Error::LimitExceeded(what) => {
+                write!(formatter, "{} exceeds the configured limit", what)
+            }
+            Error::CrossSlot => write!(formatter, "Keys in request don't hash to the same slot"),
         }
     }
 }
 
+impl Error {
+    // 数据流本身损坏，无法再确定帧的边界
+    pub(crate) fn is_fatal(&self) -> bool {
+        matches!(
+            *self,
+            Error::Io(_)
+                | Error::Eof
+                | Error::BadLengthHint
+                | Error::UnbalancedCRLF
+                | Error::ExpectedLF
+                | Error::UnknownFrameType
+                | Error::Truncated(_)
+                | Error::LimitExceeded(_)
+        )
+    }
+}
+
+// 作为错误回复返回给客户端，格式和 Redis 一致，比如
+// -ERR wrong number of arguments for 'set' command
+impl From<Error> for RedisError {
+    fn from(err: Error) -> RedisError {
+        let message = match err {
+            Error::Redis(e) => return e,
+            Error::CrossSlot => return RedisError::new(ErrorCode::CrossSlot, err.to_string()),
+            Error::BadNumContent => "value is not an integer or out of range".to_owned(),
+            ref e if e.is_fatal() => format!("Protocol error: {}", e),
+            e => e.to_string(),
+        };
+        RedisError::new(ErrorCode::Err, message)
+    }
+}
+
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self {
@@ -0,0 +1,696 @@
+use std::fmt;
+
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::ser::{Serialize, SerializeTuple, Serializer};
+
+use super::command::RespCommand;
+use super::de::UNKNOWN_VARIANT;
+use super::error::{Error, Result};
+use super::key::KEY;
+use super::reply::{Integer, Simple};
+use super::ser::name_tokens;
+
+// 参数的种类，对应 COMMAND DOCS 中的 type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgType {
+    Key,
+    String,
+    Integer,
+    // 嵌套的枚举，几个关键字中选一个
+    OneOf,
+    // 只有关键字本身，比如 SET 的 NX
+    PureToken,
+}
+
+impl ArgType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArgType::Key => "key",
+            ArgType::String => "string",
+            ArgType::Integer => "integer",
+            ArgType::OneOf => "oneof",
+            ArgType::PureToken => "pure-token",
+        }
+    }
+}
+
+impl fmt::Display for ArgType {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgInfo {
+    pub name: String,
+    pub kind: ArgType,
+    // 可以省略的参数，比如 #[resp(optional)]
+    pub optional: bool,
+    // 可以重复多次的参数，比如 #[resp(variadic)]
+    pub multiple: bool,
+    // 参数前面的关键字，比如 SET 的 EX seconds
+    pub token: Option<String>,
+}
+
+// 一条命令的元数据，格式和 COMMAND 的回复一致。
+// 命令名是小写的，多个单词的命令名用 | 连接，比如 config|get
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandInfo {
+    pub name: String,
+    // 包括命令名在内的参数个数，负数表示至少这么多个
+    pub arity: i64,
+    pub flags: Vec<String>,
+    pub first_key: i64,
+    pub last_key: i64,
+    pub step: i64,
+    pub args: Vec<ArgInfo>,
+    // COMMAND DOCS 中的说明
+    pub summary: String,
+    pub group: String,
+}
+
+// 从命令类型中得到所有命令的元数据，枚举的每一个 variant 是一条命令，
+// struct 本身是一条命令。$unknown variant 不是真正的命令，会被跳过
+pub fn command_infos<T: DeserializeOwned>() -> Result<Vec<CommandInfo>> {
+    let mut infos = vec![];
+    let mut variant = 0;
+    loop {
+        let mut tracer = CommandTracer {
+            variant,
+            variants: None,
+            name: None,
+            args: vec![],
+        };
+        T::deserialize(&mut tracer)?;
+        if let Some(name) = tracer.name {
+            if name != UNKNOWN_VARIANT {
+                infos.push(CommandInfo::new(name, tracer.args));
+            }
+        }
+        variant += 1;
+        match tracer.variants {
+            Some(n) if variant < n => {}
+            _ => return Ok(infos),
+        }
+    }
+}
+
+impl CommandInfo {
+    // 根据参数推导 arity 和 key 的位置，flags、summary 等由调用者补充
+    pub fn new(name: &str, args: Vec<ArgInfo>) -> CommandInfo {
+        let tokens = name_tokens(name);
+        let prefix = tokens.len() as i64;
+        let required = args.iter().filter(|arg| !arg.optional).count() as i64;
+        let arity = if args.iter().any(|arg| arg.optional || arg.multiple) {
+            -(prefix + required)
+        } else {
+            prefix + required
+        };
+
+        let keys: Vec<usize> = (0..args.len())
+            .filter(|&i| args[i].kind == ArgType::Key)
+            .collect();
+        let (first_key, last_key, step) = match keys.first() {
+            None => (0, 0, 0),
+            Some(&first) if args[first].multiple => {
+                // 列表中的 key，step 是列表中每一组的参数个数。
+                // 列表后面还有参数时，last_key 从末尾倒数，比如 BLPOP key [key ...] timeout 是 -2
+                let group = args[first..].iter().take_while(|arg| arg.multiple).count();
+                let trailing = args[first + group..]
+                    .iter()
+                    .filter(|arg| arg.kind != ArgType::Key)
+                    .count();
+                (prefix + first as i64, -1 - trailing as i64, group as i64)
+            }
+            Some(&first) => {
+                let last = *keys.last().unwrap();
+                let step = keys.get(1).map_or(1, |&second| second - first);
+                (prefix + first as i64, prefix + last as i64, step as i64)
+            }
+        };
+
+        CommandInfo {
+            name: tokens.join("|").to_lowercase(),
+            arity,
+            flags: vec![],
+            first_key,
+            last_key,
+            step,
+            args,
+            summary: String::new(),
+            group: "generic".to_owned(),
+        }
+    }
+}
+
+// 服务端注册的所有命令，用于回复 COMMAND、COMMAND INFO、COMMAND DOCS 和 COMMAND COUNT
+#[derive(Debug, Clone, Default)]
+pub struct CommandTable {
+    commands: Vec<CommandInfo>,
+}
+
+impl CommandTable {
+    pub fn new() -> Self {
+        CommandTable::default()
+    }
+
+    // 加入一个命令类型中的所有命令
+    pub fn register<T: DeserializeOwned>(mut self) -> Result<Self> {
+        self.commands.extend(command_infos::<T>()?);
+        Ok(self)
+    }
+
+    // 加入 #[derive(RespCommand)] 生成的命令
+    pub fn register_command<T: RespCommand>(mut self) -> Self {
+        self.commands.extend(T::command_infos());
+        self
+    }
+
+    pub(crate) fn extend(&mut self, infos: Vec<CommandInfo>) {
+        self.commands.extend(infos);
+    }
+
+    pub fn commands(&self) -> &[CommandInfo] {
+        &self.commands
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    // 命令名不区分大小写，多个单词可以用空格或者 | 分隔
+    pub fn get(&self, name: &str) -> Option<&CommandInfo> {
+        let name = normalize(name);
+        self.commands.iter().find(|cmd| cmd.name == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut CommandInfo> {
+        let name = normalize(name);
+        self.commands.iter_mut().find(|cmd| cmd.name == name)
+    }
+
+    // COMMAND INFO 的回复，不存在的命令是 null
+    pub fn info(&self, names: &[&str]) -> Vec<Option<&CommandInfo>> {
+        names.iter().map(|name| self.get(name)).collect()
+    }
+
+    // COMMAND DOCS 的回复，没有给出命令名时返回全部命令，不存在的命令被忽略
+    pub fn docs(&self, names: &[&str]) -> CommandDocs<'_> {
+        let commands = if names.is_empty() {
+            self.commands.iter().collect()
+        } else {
+            names.iter().filter_map(|name| self.get(name)).collect()
+        };
+        CommandDocs { commands }
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.split([' ', '|'])
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join("|")
+        .to_lowercase()
+}
+
+// COMMAND 的回复中，每一条命令是
+// [name, arity, [flags], first_key, last_key, step]
+impl Serialize for CommandInfo {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let flags: Vec<Simple<&str>> = self.flags.iter().map(|f| Simple(&f[..])).collect();
+        let mut tuple = serializer.serialize_tuple(6)?;
+        tuple.serialize_element(&self.name)?;
+        tuple.serialize_element(&Integer(self.arity))?;
+        tuple.serialize_element(&flags)?;
+        tuple.serialize_element(&Integer(self.first_key))?;
+        tuple.serialize_element(&Integer(self.last_key))?;
+        tuple.serialize_element(&Integer(self.step))?;
+        tuple.end()
+    }
+}
+
+// COMMAND DOCS 的回复，RESP2 中 map 写成键值交替的数组：
+// [name, [summary, ..., group, ..., arguments, [...]], ...]
+pub struct CommandDocs<'a> {
+    commands: Vec<&'a CommandInfo>,
+}
+
+struct Doc<'a>(&'a CommandInfo);
+
+struct ArgDoc<'a>(&'a ArgInfo);
+
+impl Serialize for CommandDocs<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(self.commands.len() * 2)?;
+        for cmd in &self.commands {
+            tuple.serialize_element(&cmd.name)?;
+            tuple.serialize_element(&Doc(cmd))?;
+        }
+        tuple.end()
+    }
+}
+
+impl Serialize for Doc<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let args: Vec<ArgDoc> = self.0.args.iter().map(ArgDoc).collect();
+        let mut tuple = serializer.serialize_tuple(6)?;
+        tuple.serialize_element("summary")?;
+        tuple.serialize_element(&self.0.summary)?;
+        tuple.serialize_element("group")?;
+        tuple.serialize_element(&self.0.group)?;
+        tuple.serialize_element("arguments")?;
+        tuple.serialize_element(&args)?;
+        tuple.end()
+    }
+}
+
+impl Serialize for ArgDoc<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut flags = vec![];
+        if self.0.optional {
+            flags.push(Simple("optional"));
+        }
+        if self.0.multiple {
+            flags.push(Simple("multiple"));
+        }
+        let mut len = 4;
+        if self.0.token.is_some() {
+            len += 2;
+        }
+        if !flags.is_empty() {
+            len += 2;
+        }
+        let mut tuple = serializer.serialize_tuple(len)?;
+        tuple.serialize_element("name")?;
+        tuple.serialize_element(&self.0.name)?;
+        tuple.serialize_element("type")?;
+        tuple.serialize_element(self.0.kind.as_str())?;
+        if let Some(token) = &self.0.token {
+            tuple.serialize_element("token")?;
+            tuple.serialize_element(token)?;
+        }
+        if !flags.is_empty() {
+            tuple.serialize_element("flags")?;
+            tuple.serialize_element(&flags)?;
+        }
+        tuple.end()
+    }
+}
+
+// 追踪命令类型的结构：反序列化时不读取任何数据，而是记录类型请求的每一个参数，
+// 并给出一个占位的值让 visitor 继续。枚举每次只能追踪一个 variant
+struct CommandTracer {
+    // 这一次选择的 variant
+    variant: usize,
+    variants: Option<usize>,
+    name: Option<&'static str>,
+    args: Vec<ArgInfo>,
+}
+
+// 正在追踪的参数的状态，从外层传递到里层
+#[derive(Clone)]
+struct ArgState {
+    name: String,
+    key: bool,
+}
+
+impl ArgState {
+    fn named(name: String) -> ArgState {
+        ArgState { name, key: false }
+    }
+}
+
+struct ArgTracer<'a> {
+    // 为 None 时只给出占位值，不记录参数，比如嵌套命令的内容
+    args: Option<&'a mut Vec<ArgInfo>>,
+    state: ArgState,
+}
+
+impl ArgTracer<'_> {
+    fn record(&mut self, kind: ArgType) {
+        let kind = if self.state.key { ArgType::Key } else { kind };
+        if let Some(args) = self.args.as_mut() {
+            args.push(ArgInfo {
+                name: self.state.name.clone(),
+                kind,
+                optional: false,
+                multiple: false,
+                token: None,
+            });
+        }
+    }
+}
+
+fn not_traceable(what: &str) -> Error {
+    Error::Message(format!("can't trace {} in command", what))
+}
+
+impl<'de> de::Deserializer<'de> for &mut CommandTracer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(not_traceable("self-describing type"))
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.name = Some(name);
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.name = Some(name);
+        visitor.visit_newtype_struct(ArgTracer {
+            args: Some(&mut self.args),
+            state: ArgState::named("arg".to_owned()),
+        })
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.name = Some(name);
+        visitor.visit_seq(TraceSeq::tuple(Some(&mut self.args), len))
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.name = Some(name);
+        visitor.visit_seq(TraceSeq::fields(Some(&mut self.args), fields))
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.variants = Some(variants.len());
+        let name = *variants
+            .get(self.variant)
+            .ok_or_else(|| not_traceable("enum without variants"))?;
+        self.name = Some(name);
+        visitor.visit_enum(TraceEnum {
+            name,
+            args: Some(&mut self.args),
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit seq tuple map identifier ignored_any
+    }
+}
+
+macro_rules! trace_integer {
+    ($($method: ident => $visit: ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+                self.record(ArgType::Integer);
+                visitor.$visit(0)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ArgTracer<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(not_traceable("self-describing type"))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+        self.record(ArgType::String);
+        visitor.visit_bool(false)
+    }
+
+    trace_integer! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(not_traceable("float"))
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(not_traceable("float"))
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+        self.record(ArgType::String);
+        visitor.visit_char(' ')
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+        self.record(ArgType::String);
+        visitor.visit_string(String::new())
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+        self.record(ArgType::String);
+        visitor.visit_byte_buf(vec![])
+    }
+
+    // None 写成 null bulk string，仍然占一个参数的位置，所以不是可选的参数
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(mut self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if name == KEY {
+            self.state.key = true;
+            if self.state.name == "arg" {
+                self.state.name = "key".to_owned();
+            }
+        }
+        visitor.visit_newtype_struct(self)
+    }
+
+    // 列表写成嵌套的数组，只占一个参数，不能重复。
+    // 元素只追踪一个，给出占位值，不记录参数
+    fn deserialize_seq<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+        self.record(ArgType::String);
+        let state = self.state.clone();
+        visitor.visit_seq(TraceSeq {
+            args: None,
+            states: vec![state],
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        let states = (0..len).map(|_| self.state.clone()).collect();
+        visitor.visit_seq(TraceSeq {
+            args: self.args,
+            states,
+        })
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(not_traceable("map"))
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(TraceSeq::fields(self.args, fields))
+    }
+
+    // 参数中的枚举是几个关键字中的一个，只记录一个参数，总是选择第一个 variant
+    fn deserialize_enum<V>(
+        mut self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.record(ArgType::OneOf);
+        let name = *variants
+            .first()
+            .ok_or_else(|| not_traceable("enum without variants"))?;
+        visitor.visit_enum(TraceEnum { name, args: None })
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+}
+
+// 依次追踪每一个元素，元素的个数由 states 决定
+struct TraceSeq<'a> {
+    args: Option<&'a mut Vec<ArgInfo>>,
+    // 倒序存放，方便 pop
+    states: Vec<ArgState>,
+}
+
+impl<'a> TraceSeq<'a> {
+    fn tuple(args: Option<&'a mut Vec<ArgInfo>>, len: usize) -> Self {
+        let states = (0..len)
+            .map(|_| ArgState::named("arg".to_owned()))
+            .collect();
+        TraceSeq { args, states }
+    }
+
+    fn fields(args: Option<&'a mut Vec<ArgInfo>>, fields: &[&str]) -> Self {
+        let states = fields
+            .iter()
+            .rev()
+            .map(|f| ArgState::named(f.to_string()))
+            .collect();
+        TraceSeq { args, states }
+    }
+}
+
+impl<'de> SeqAccess<'de> for TraceSeq<'_> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.states.pop() {
+            Some(state) => seed
+                .deserialize(ArgTracer {
+                    args: self.args.as_deref_mut(),
+                    state,
+                })
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.states.len())
+    }
+}
+
+struct TraceEnum<'a> {
+    name: &'static str,
+    args: Option<&'a mut Vec<ArgInfo>>,
+}
+
+impl<'de, 'a> EnumAccess<'de> for TraceEnum<'a> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let de: de::value::StrDeserializer<Error> = self.name.into_deserializer();
+        Ok((seed.deserialize(de)?, self))
+    }
+}
+
+impl<'de, 'a> VariantAccess<'de> for TraceEnum<'a> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(mut self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(ArgTracer {
+            args: self.args.as_deref_mut(),
+            state: ArgState::named("arg".to_owned()),
+        })
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(TraceSeq::tuple(self.args, len))
+    }
+
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(TraceSeq::fields(self.args, fields))
+    }
+}
@@ -0,0 +1,86 @@
+use std::ops::{Deref, DerefMut};
+
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+use super::args::ArgsCollector;
+use super::error::Result;
+
+// 标记 key 参数的 newtype 名，序列化和反序列化时透明，只在提取 key 时生效
+pub(crate) const KEY: &str = "$key";
+
+// 命令中的 key 参数，比如 struct Get { key: Key<String> }。
+// 每个字段写成一个参数，Key<Vec<String>> 会写成嵌套的数组，Redis 不接受。
+// DEL 这样接受多个 key 的命令用 #[derive(RespCommand)] 和 #[resp(key, variadic)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Key<T>(pub T);
+
+impl<T> Key<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Key<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Key<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> From<T> for Key<T> {
+    fn from(value: T) -> Key<T> {
+        Key(value)
+    }
+}
+
+impl<T: Serialize> Serialize for Key<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(KEY, &self.0)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Key<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        // 其它格式不认识 $key，按照 newtype 处理即可
+        struct KeyVisitor<T>(std::marker::PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> serde::de::Visitor<'de> for KeyVisitor<T> {
+            type Value = Key<T>;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("key")
+            }
+
+            fn visit_newtype_struct<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> std::result::Result<Key<T>, D::Error> {
+                T::deserialize(deserializer).map(Key)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(KEY, KeyVisitor(std::marker::PhantomData))
+    }
+}
+
+// 取出命令中所有用 Key 标记的参数，按照出现的顺序，和 COMMAND GETKEYS 的结果一致
+pub fn get_keys<T>(value: &T) -> Result<Vec<Vec<u8>>>
+where
+    T: ?Sized + Serialize,
+{
+    let mut collector = ArgsCollector::default();
+    value.serialize(&mut collector)?;
+    let ArgsCollector { mut args, keys, .. } = collector;
+    Ok(keys
+        .into_iter()
+        .map(|i| std::mem::take(&mut args[i]))
+        .collect())
+}
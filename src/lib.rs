@@ -1,9 +1,37 @@
+pub mod aof;
+mod args;
+pub mod bytes;
+pub mod client;
+pub mod cluster;
+pub mod command;
 pub mod de;
 mod error;
+pub mod info;
+pub mod key;
+pub mod multiplexed;
+pub mod pool;
+pub mod raw;
+pub mod reader;
+pub mod reply;
+pub mod router;
+pub mod scan;
 pub mod ser;
+pub mod writer;
 
 // pub use de::from_bytes;
 pub use de::from_reader;
-pub use ser::to_bytes;
+pub use reader::{Event, Limits, Reader};
+pub use scan::scan_frame;
+pub use ser::{to_bytes, to_reply, to_writer, to_writer_vectored, write_command_vectored};
+pub use writer::{VectoredWriter, Writer};
 
+pub use client::Client;
+pub use command::{Command, CommandRef, RespCommand};
 pub use error::Error;
+pub use key::{get_keys, Key};
+pub use multiplexed::MultiplexedClient;
+pub use pool::Pool;
+pub use raw::{RawFrame, RawFrameRef};
+pub use reply::{ErrorCode, Integer, RedisError, Simple};
+#[cfg(feature = "derive")]
+pub use serde_resp_derive::RespCommand;
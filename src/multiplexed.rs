@@ -0,0 +1,344 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::mem;
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, JoinHandle};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::de::Deserializer;
+use super::error::{Error, Result};
+use super::ser::to_bytes;
+
+type Reader = Box<dyn Read + Send>;
+type ShutdownFn = Box<dyn Fn() + Send + Sync>;
+
+// 默认最多有多少个请求在等待回复，超过之后新的请求要等待前面的回复
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+// 多路复用的客户端，对外提供 future 接口，内部用阻塞的读写线程实现。
+// 多个任务共享一个连接，请求先进入队列，由写线程批量写入；读线程按照 FIFO
+// 顺序把回复交给对应的 future。不依赖任何异步运行时，返回的 ReplyFuture
+// 可以在任意执行器中等待，但是每个客户端固定占用两个线程
+#[derive(Clone)]
+pub struct MultiplexedClient {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    shared: Arc<Shared>,
+    // 关闭连接，让阻塞在 read 上的读线程退出
+    shutdown: ShutdownFn,
+    threads: Vec<JoinHandle<()>>,
+}
+
+// 客户端和所有的 future 都丢弃之后，关闭连接并等待读写线程退出
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.shared
+            .close(io::ErrorKind::NotConnected, "client is dropped");
+        (self.shutdown)();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+struct State {
+    // 已经编码但是还没有写入连接的请求
+    queue: Vec<u8>,
+    // 按照发送顺序等待回复的请求，包括还在 queue 中的
+    in_flight: VecDeque<Arc<dyn Pending>>,
+    capacity: usize,
+    // 等待队列空出位置的 future
+    blocked: Vec<Waker>,
+    closed: Option<(io::ErrorKind, String)>,
+}
+
+impl Shared {
+    // 连接不可用，所有等待中的请求都返回错误
+    fn close(&self, kind: io::ErrorKind, message: &str) {
+        let mut state = self.state.lock().unwrap();
+        let closed = state
+            .closed
+            .get_or_insert_with(|| (kind, message.to_owned()))
+            .clone();
+        state.queue.clear();
+        for pending in state.in_flight.drain(..) {
+            pending.fail(closed_error(&closed));
+        }
+        for waker in state.blocked.drain(..) {
+            waker.wake();
+        }
+        self.changed.notify_all();
+    }
+}
+
+fn closed_error((kind, message): &(io::ErrorKind, String)) -> Error {
+    Error::Io(io::Error::new(*kind, message.clone()))
+}
+
+impl MultiplexedClient {
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let handle = stream.try_clone()?;
+        let shutdown = move || {
+            let _ = handle.shutdown(Shutdown::Both);
+        };
+        Ok(Self::new(stream.try_clone()?, stream, shutdown))
+    }
+
+    // 任意的读写两端。shutdown 要让阻塞中的 read 返回，例如 TcpStream::shutdown，
+    // 客户端丢弃时调用它并等待读写线程退出
+    pub fn new<R, W, F>(reader: R, writer: W, shutdown: F) -> Self
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+        F: Fn() + Send + Sync + 'static,
+    {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                queue: vec![],
+                in_flight: VecDeque::new(),
+                capacity: DEFAULT_CAPACITY,
+                blocked: vec![],
+                closed: None,
+            }),
+            changed: Condvar::new(),
+        });
+        let de = Deserializer::from_reader(Box::new(reader) as Reader);
+        let read_shared = shared.clone();
+        let write_shared = shared.clone();
+        let threads = vec![
+            thread::spawn(move || read_loop(read_shared, de)),
+            thread::spawn(move || write_loop(write_shared, writer)),
+        ];
+        MultiplexedClient {
+            inner: Arc::new(Inner {
+                shared,
+                shutdown: Box::new(shutdown),
+                threads,
+            }),
+        }
+    }
+
+    // 最多有多少个请求在等待回复，至少为 1
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut state = self.inner.shared.state.lock().unwrap();
+        state.capacity = capacity.max(1);
+        for waker in state.blocked.drain(..) {
+            waker.wake();
+        }
+    }
+
+    // 命令在第一次 poll 时进入队列。进入队列之后丢弃 future 不会打乱后续的回复
+    pub fn query<C, T>(&self, cmd: &C) -> ReplyFuture<T>
+    where
+        C: Serialize,
+        T: DeserializeOwned + Send + 'static,
+    {
+        ReplyFuture {
+            inner: self.inner.clone(),
+            state: ReplyState::Request(to_bytes(cmd)),
+        }
+    }
+}
+
+fn read_loop(shared: Arc<Shared>, mut de: Deserializer<Reader>) {
+    loop {
+        let pending = {
+            let mut state = shared.state.lock().unwrap();
+            loop {
+                if state.closed.is_some() {
+                    return;
+                }
+                if let Some(pending) = state.in_flight.front() {
+                    break pending.clone();
+                }
+                state = shared.changed.wait(state).unwrap();
+            }
+        };
+        pending.complete(&mut de);
+        if de.is_broken() {
+            pending.publish();
+            shared.close(io::ErrorKind::BrokenPipe, "connection is broken");
+            return;
+        }
+        // 先让出队列中的位置再唤醒 future，醒来之后马上可以发送新的请求
+        let mut state = shared.state.lock().unwrap();
+        state.in_flight.pop_front();
+        pending.publish();
+        for waker in state.blocked.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+fn write_loop<W: Write>(shared: Arc<Shared>, mut writer: W) {
+    loop {
+        // 写入期间进入队列的请求会在下一次一起写入
+        let batch = {
+            let mut state = shared.state.lock().unwrap();
+            loop {
+                if state.closed.is_some() {
+                    return;
+                }
+                if !state.queue.is_empty() {
+                    break mem::take(&mut state.queue);
+                }
+                state = shared.changed.wait(state).unwrap();
+            }
+        };
+        if let Err(e) = writer.write_all(&batch).and_then(|_| writer.flush()) {
+            shared.close(e.kind(), &e.to_string());
+            return;
+        }
+    }
+}
+
+// 等待回复的请求，回复的类型由各自的 future 决定
+trait Pending: Send + Sync {
+    // 从连接中读取这个请求的回复，暂时不交给 future
+    fn complete(&self, de: &mut Deserializer<Reader>);
+    // 把读到的回复交给 future
+    fn publish(&self);
+    fn fail(&self, e: Error);
+}
+
+struct Slot<T> {
+    state: Mutex<SlotState<T>>,
+}
+
+struct SlotState<T> {
+    // 已经读到，但是还没有交给 future 的回复
+    staged: Option<Result<T>>,
+    reply: Option<Result<T>>,
+    waker: Option<Waker>,
+    // future 已经被丢弃，回复直接跳过
+    cancelled: bool,
+}
+
+impl<T> Slot<T> {
+    fn set(&self, reply: Result<T>) {
+        let mut state = self.state.lock().unwrap();
+        if state.reply.is_none() {
+            state.reply = Some(reply);
+        }
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T: DeserializeOwned + Send> Pending for Slot<T> {
+    fn complete(&self, de: &mut Deserializer<Reader>) {
+        if self.state.lock().unwrap().cancelled {
+            let _ = de.skip_frame();
+        } else {
+            let reply = de.next_reply(PhantomData);
+            self.state.lock().unwrap().staged = Some(reply);
+        }
+    }
+
+    fn publish(&self) {
+        let staged = self.state.lock().unwrap().staged.take();
+        if let Some(reply) = staged {
+            self.set(reply);
+        }
+    }
+
+    fn fail(&self, e: Error) {
+        self.set(Err(e));
+    }
+}
+
+pub struct ReplyFuture<T> {
+    inner: Arc<Inner>,
+    state: ReplyState<T>,
+}
+
+enum ReplyState<T> {
+    // 还没有进入队列
+    Request(Result<Vec<u8>>),
+    Sent(Arc<Slot<T>>),
+    Done,
+}
+
+impl<T: DeserializeOwned + Send + 'static> Future for ReplyFuture<T> {
+    type Output = Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<T>> {
+        let this = &mut *self;
+        if let ReplyState::Request(request) = &mut this.state {
+            let request = match request {
+                Ok(request) => request,
+                Err(_) => match mem::replace(&mut this.state, ReplyState::Done) {
+                    ReplyState::Request(Err(e)) => return Poll::Ready(Err(e)),
+                    _ => unreachable!(),
+                },
+            };
+            let shared = &this.inner.shared;
+            let mut state = shared.state.lock().unwrap();
+            if let Some(closed) = &state.closed {
+                let e = closed_error(closed);
+                drop(state);
+                this.state = ReplyState::Done;
+                return Poll::Ready(Err(e));
+            }
+            if state.in_flight.len() >= state.capacity {
+                state.blocked.push(cx.waker().clone());
+                return Poll::Pending;
+            }
+            let slot = Arc::new(Slot {
+                state: Mutex::new(SlotState {
+                    staged: None,
+                    reply: None,
+                    waker: None,
+                    cancelled: false,
+                }),
+            });
+            state.queue.extend_from_slice(request);
+            state.in_flight.push_back(slot.clone());
+            shared.changed.notify_all();
+            drop(state);
+            this.state = ReplyState::Sent(slot);
+        }
+        match &this.state {
+            ReplyState::Sent(slot) => {
+                let mut state = slot.state.lock().unwrap();
+                match state.reply.take() {
+                    Some(reply) => {
+                        drop(state);
+                        this.state = ReplyState::Done;
+                        Poll::Ready(reply)
+                    }
+                    None => {
+                        state.waker = Some(cx.waker().clone());
+                        Poll::Pending
+                    }
+                }
+            }
+            _ => panic!("ReplyFuture polled after completion"),
+        }
+    }
+}
+
+impl<T> Drop for ReplyFuture<T> {
+    fn drop(&mut self) {
+        if let ReplyState::Sent(slot) = &self.state {
+            slot.state.lock().unwrap().cancelled = true;
+        }
+    }
+}
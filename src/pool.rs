@@ -0,0 +1,236 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use super::client::Client;
+use super::command::Command;
+use super::error::{Error, Result};
+
+pub const DEFAULT_MAX_SIZE: usize = 16;
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+type Connect<R, W> = Box<dyn Fn() -> Result<Client<R, W>> + Send + Sync>;
+
+// 线程安全的连接池。借出的连接归还时，如果出现过协议错误或者还有没读完的回复，
+// 直接丢弃；空闲太久的连接在借出前丢弃，其余的连接借出前用 PING 检查
+pub struct Pool<R, W> {
+    inner: Arc<PoolInner<R, W>>,
+}
+
+impl<R, W> Clone for Pool<R, W> {
+    fn clone(&self) -> Self {
+        Pool {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+struct PoolInner<R, W> {
+    connect: Connect<R, W>,
+    state: Mutex<PoolState<R, W>>,
+    // 有连接归还或者被丢弃
+    released: Condvar,
+}
+
+struct PoolState<R, W> {
+    idle: Vec<Idle<R, W>>,
+    // 已经创建的连接数，包括借出的
+    size: usize,
+    max_size: usize,
+    idle_timeout: Option<Duration>,
+    // 连接数已满时最多等待多久，None 表示一直等待
+    wait_timeout: Option<Duration>,
+    health_check: bool,
+}
+
+struct Idle<R, W> {
+    client: Client<R, W>,
+    since: Instant,
+}
+
+impl Pool<TcpStream, TcpStream> {
+    // 地址只解析一次
+    pub fn tcp<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+        Ok(Pool::new(move || Client::connect(&addrs[..])))
+    }
+}
+
+impl<R: Read, W: Write> Pool<R, W> {
+    pub fn new<F>(connect: F) -> Self
+    where
+        F: Fn() -> Result<Client<R, W>> + Send + Sync + 'static,
+    {
+        Pool {
+            inner: Arc::new(PoolInner {
+                connect: Box::new(connect),
+                state: Mutex::new(PoolState {
+                    idle: vec![],
+                    size: 0,
+                    max_size: DEFAULT_MAX_SIZE,
+                    idle_timeout: Some(DEFAULT_IDLE_TIMEOUT),
+                    wait_timeout: None,
+                    health_check: true,
+                }),
+                released: Condvar::new(),
+            }),
+        }
+    }
+
+    // 最多创建多少个连接，至少为 1
+    pub fn set_max_size(&self, max_size: usize) {
+        self.inner.state.lock().unwrap().max_size = max_size.max(1);
+        self.inner.released.notify_all();
+    }
+
+    pub fn set_idle_timeout(&self, timeout: Option<Duration>) {
+        self.inner.state.lock().unwrap().idle_timeout = timeout;
+    }
+
+    pub fn set_wait_timeout(&self, timeout: Option<Duration>) {
+        self.inner.state.lock().unwrap().wait_timeout = timeout;
+    }
+
+    // 借出空闲连接之前是否用 PING 检查
+    pub fn set_health_check(&self, enabled: bool) {
+        self.inner.state.lock().unwrap().health_check = enabled;
+    }
+
+    // 已经创建的连接数，包括借出的
+    pub fn size(&self) -> usize {
+        self.inner.state.lock().unwrap().size
+    }
+
+    pub fn idle(&self) -> usize {
+        self.inner.state.lock().unwrap().idle.len()
+    }
+
+    // 借出一个连接，优先使用最近归还的空闲连接。连接数已满时等待其他线程归还
+    pub fn get(&self) -> Result<PooledClient<R, W>> {
+        let inner = &*self.inner;
+        let mut state = inner.state.lock().unwrap();
+        // 超出 Instant 范围的等待时间相当于一直等待
+        let deadline = state
+            .wait_timeout
+            .and_then(|timeout| Instant::now().checked_add(timeout));
+        loop {
+            if let Some(timeout) = state.idle_timeout {
+                let before = state.idle.len();
+                state.idle.retain(|idle| idle.since.elapsed() < timeout);
+                let evicted = before - state.idle.len();
+                if evicted > 0 {
+                    state.size -= evicted;
+                    // 空出的位置这里最多用一个，其余的让等待中的线程去建立连接
+                    inner.released.notify_all();
+                }
+            }
+            if let Some(Idle { mut client, .. }) = state.idle.pop() {
+                if !state.health_check {
+                    return Ok(self.lend(client));
+                }
+                drop(state);
+                if ping(&mut client) {
+                    return Ok(self.lend(client));
+                }
+                drop(client);
+                state = inner.state.lock().unwrap();
+                state.size -= 1;
+                continue;
+            }
+            if state.size < state.max_size {
+                state.size += 1;
+                drop(state);
+                return match (inner.connect)() {
+                    Ok(client) => Ok(self.lend(client)),
+                    Err(e) => {
+                        inner.state.lock().unwrap().size -= 1;
+                        inner.released.notify_one();
+                        Err(e)
+                    }
+                };
+            }
+            state = match deadline {
+                None => inner.released.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        let e =
+                            io::Error::new(io::ErrorKind::TimedOut, "connection pool timed out");
+                        return Err(Error::Io(e));
+                    }
+                    inner
+                        .released
+                        .wait_timeout(state, deadline - now)
+                        .unwrap()
+                        .0
+                }
+            };
+        }
+    }
+
+    fn lend(&self, client: Client<R, W>) -> PooledClient<R, W> {
+        PooledClient {
+            client: Some(client),
+            pool: self.inner.clone(),
+        }
+    }
+}
+
+fn ping<R: Read, W: Write>(client: &mut Client<R, W>) -> bool {
+    match client.query::<_, String>(&Command::new("PING")) {
+        Ok(reply) => reply == "PONG",
+        Err(_) => false,
+    }
+}
+
+// 借出的连接，drop 时归还给连接池
+pub struct PooledClient<R, W> {
+    client: Option<Client<R, W>>,
+    pool: Arc<PoolInner<R, W>>,
+}
+
+impl<R, W> PooledClient<R, W> {
+    // 不再归还这个连接，例如连接的状态已经被修改
+    pub fn discard(mut self) {
+        self.client = None;
+        self.pool.state.lock().unwrap().size -= 1;
+        self.pool.released.notify_one();
+    }
+}
+
+impl<R, W> Deref for PooledClient<R, W> {
+    type Target = Client<R, W>;
+
+    fn deref(&self) -> &Client<R, W> {
+        self.client.as_ref().unwrap()
+    }
+}
+
+impl<R, W> DerefMut for PooledClient<R, W> {
+    fn deref_mut(&mut self) -> &mut Client<R, W> {
+        self.client.as_mut().unwrap()
+    }
+}
+
+impl<R, W> Drop for PooledClient<R, W> {
+    fn drop(&mut self) {
+        let client = match self.client.take() {
+            Some(client) => client,
+            None => return,
+        };
+        let mut state = self.pool.state.lock().unwrap();
+        // 出现过协议错误，或者还有回复没有读取，连接上的数据已经无法对齐
+        if client.is_broken() || client.pending_replies() > 0 {
+            state.size -= 1;
+        } else {
+            state.idle.push(Idle {
+                client,
+                since: Instant::now(),
+            });
+        }
+        drop(state);
+        self.pool.released.notify_one();
+    }
+}
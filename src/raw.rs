@@ -0,0 +1,141 @@
+use std::fmt;
+use std::ops::Deref;
+
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use super::de::from_reader;
+use super::error::{Error, Result};
+use super::scan::scan_frame;
+
+// 原样保存一个帧的 newtype 名。de::Deserializer 据此读出下一帧的原始字节，
+// ser::Serializer 把字节原样写回，其它格式只会看到里面的字节
+pub(crate) const RAW_FRAME: &str = "$raw";
+
+// 一个完整的 resp 帧的原始字节，转发或缓存时不需要解析其中的内容，
+// 比如 struct Set { key: Key<String>, value: RawFrame }，或者直接把整个回复读成 RawFrame
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RawFrame(Vec<u8>);
+
+// 借用的 RawFrame，只用于序列化，避免为了写出已有的字节而复制一份
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawFrameRef<'a>(&'a [u8]);
+
+// bytes 必须恰好是一个完整的帧
+fn check_frame(bytes: &[u8]) -> Result<()> {
+    match scan_frame(bytes)? {
+        Some(len) if len == bytes.len() => Ok(()),
+        Some(_) => Err(Error::TrailingBytes),
+        None => Err(Error::Eof),
+    }
+}
+
+impl RawFrame {
+    pub fn new(bytes: Vec<u8>) -> Result<Self> {
+        check_frame(&bytes)?;
+        Ok(RawFrame(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    pub fn as_frame_ref(&self) -> RawFrameRef<'_> {
+        RawFrameRef(&self.0)
+    }
+
+    // 之后再把保存的帧解析成具体的类型
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T> {
+        self.as_frame_ref().decode()
+    }
+}
+
+impl<'a> RawFrameRef<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        check_frame(bytes)?;
+        Ok(RawFrameRef(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    pub fn into_owned(self) -> RawFrame {
+        RawFrame(self.0.to_vec())
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T> {
+        from_reader(self.0)
+    }
+}
+
+impl Deref for RawFrame {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<'a> From<RawFrameRef<'a>> for RawFrame {
+    fn from(frame: RawFrameRef<'a>) -> RawFrame {
+        frame.into_owned()
+    }
+}
+
+// 帧的内容用 serialize_bytes 交给 Serializer
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl Serialize for RawFrame {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.as_frame_ref().serialize(serializer)
+    }
+}
+
+impl Serialize for RawFrameRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(RAW_FRAME, &Bytes(self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for RawFrame {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct RawFrameVisitor;
+
+        impl<'de> Visitor<'de> for RawFrameVisitor {
+            type Value = RawFrame;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("raw resp frame")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<RawFrame, E> {
+                self.visit_byte_buf(v.to_vec())
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> std::result::Result<RawFrame, E> {
+                RawFrame::new(v).map_err(E::custom)
+            }
+
+            // 其它格式不认识 $raw，按照 newtype 处理，里面应当是帧的字节
+            fn visit_newtype_struct<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> std::result::Result<RawFrame, D::Error> {
+                deserializer.deserialize_byte_buf(self)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(RAW_FRAME, RawFrameVisitor)
+    }
+}
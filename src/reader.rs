@@ -0,0 +1,489 @@
+use std::io::{self, BufRead, Read};
+use std::mem;
+
+use super::error::{Error, Result};
+
+const CR: u8 = b'\r';
+pub(crate) const LF: u8 = b'\n';
+
+// 逐个读取 resp 元素的底层接口，de::Deserializer 也建立在它之上。
+// 适合 serde 不方便处理的场景，比如流式处理上千万个元素的 SCAN 回复
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    // 之后的 len 个元素属于这个数组
+    ArrayStart(usize),
+    Bulk(Vec<u8>),
+    // 超过 chunk_size 的 bulk string 只给出长度，内容用 read_chunk 分块读取
+    BulkStart(usize),
+    Integer(i64),
+    SimpleString(Vec<u8>),
+    Error(Vec<u8>),
+    // null bulk string 和 null array
+    Null,
+}
+
+// resp 元素的头部，即类型符号以及它所在的这一行
+pub(crate) enum Header {
+    Array(Option<usize>),
+    // bulk string 的数据部分还没有读取
+    Bulk(Option<usize>),
+    Simple(Vec<u8>),
+    Error(Vec<u8>),
+    Integer(Vec<u8>),
+}
+
+// 解析时的限制，防止恶意或者损坏的数据占用过多内存
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    // 和 Redis 的 proto-max-bulk-len 默认值相同
+    pub max_bulk_len: usize,
+    pub max_array_len: usize,
+    // 数组的嵌套层数
+    pub max_depth: usize,
+    // simple string、错误回复等一行内容的长度，不包括 CRLF
+    pub max_line_len: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_bulk_len: 512 * 1024 * 1024,
+            max_array_len: i32::MAX as usize,
+            max_depth: 128,
+            max_line_len: 64 * 1024,
+        }
+    }
+}
+
+impl Limits {
+    fn check(value: usize, max: usize, what: &'static str) -> Result<()> {
+        if value > max {
+            return Err(Error::LimitExceeded(what));
+        }
+        Ok(())
+    }
+
+    // 进入新的一层数组之前，depth 是已有的层数
+    pub(crate) fn check_depth(&self, depth: usize) -> Result<()> {
+        Limits::check(depth + 1, self.max_depth, "nesting depth")
+    }
+}
+
+pub struct Reader<R> {
+    reader: io::BufReader<R>,
+    byte_offset: usize,
+    // peek 到的头部。BufReader 只在缓冲区为空时才会读取，所以不能直接 peek 多个字节
+    peeked: Option<Header>,
+    // peeked 头部的原始字节，RawFrame 从已经 peek 的头部开始时需要补上它
+    peeked_raw: Vec<u8>,
+    // 当前帧中，每一层还没有解析完的数组剩余的元素个数
+    pending: Vec<usize>,
+    // 已经完整读取的顶层元素的个数
+    frames: usize,
+    limits: Limits,
+    // 正在读取 RawFrame，记录读到的原始字节
+    capture: Option<Vec<u8>>,
+    // 正在分块读取的 bulk string 还没有读取的字节数
+    bulk_left: Option<usize>,
+    chunk_size: Option<usize>,
+}
+
+impl<R: io::Read> Reader<R> {
+    pub fn new(r: R) -> Self {
+        Reader {
+            reader: io::BufReader::new(r),
+            byte_offset: 0,
+            peeked: None,
+            peeked_raw: vec![],
+            pending: vec![],
+            frames: 0,
+            limits: Limits::default(),
+            capture: None,
+            bulk_left: None,
+            chunk_size: None,
+        }
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    // bulk string 超过 size 时，next_event 返回 BulkStart，None 表示总是完整读取
+    pub fn set_chunk_size(&mut self, size: Option<usize>) {
+        self.chunk_size = size;
+    }
+
+    pub fn bytes_offset(&self) -> usize {
+        self.byte_offset
+    }
+
+    // 是否还有已经读进缓冲区、没有处理的数据。为 false 时，下一次读取可能会阻塞
+    pub fn has_buffered(&self) -> bool {
+        self.peeked.is_some() || !self.reader.buffer().is_empty()
+    }
+
+    // 还没有读完的数组的层数，为 0 时说明一帧已经读完
+    pub fn depth(&self) -> usize {
+        self.pending.len()
+    }
+
+    // 读取下一个元素，数据在两帧之间读完时返回 None。
+    // 上一个 BulkStart 的内容没有读完时，剩下的部分会被跳过
+    pub fn next_event(&mut self) -> Result<Option<Event>> {
+        self.skip_chunks()?;
+        if self.pending.is_empty() && self.peeked.is_none() && self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        let event = match self.next_header()? {
+            Header::Array(Some(len)) => Event::ArrayStart(len),
+            Header::Array(None) | Header::Bulk(None) => Event::Null,
+            Header::Bulk(Some(len)) => match self.chunk_size {
+                Some(size) if len > size => {
+                    self.bulk_left = Some(len);
+                    Event::BulkStart(len)
+                }
+                _ => Event::Bulk(self.read_bulk_data(len)?),
+            },
+            Header::Simple(line) => Event::SimpleString(line),
+            Header::Error(line) => Event::Error(line),
+            Header::Integer(line) => Event::Integer(parse_integer(&line)?),
+        };
+        Ok(Some(event))
+    }
+
+    // 读取 BulkStart 之后的内容，最多填满 buf。buf 不为空时返回 0 表示已经读完
+    pub fn read_chunk(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let left = match self.bulk_left {
+            Some(left) => left,
+            None => return Ok(0),
+        };
+        let len = buf.len().min(left);
+        let n = self.reader.read(&mut buf[..len])?;
+        if n == 0 && len > 0 {
+            return Err(Error::Eof);
+        }
+        self.byte_offset += n;
+        if let Some(raw) = &mut self.capture {
+            raw.extend_from_slice(&buf[..n]);
+        }
+        self.bulk_left = Some(left - n);
+        if left == n {
+            self.finish_chunks()?;
+        }
+        Ok(n)
+    }
+
+    // 把下一个 bulk string 的内容分块写入 writer，不在内存中保存完整的内容，
+    // 返回它的长度，null 返回 None。和 Deserializer 一样，simple string 和 integer 也当作内容
+    pub fn read_bulk_to<W: io::Write>(&mut self, writer: &mut W) -> Result<Option<usize>> {
+        match self.peek_header()? {
+            Header::Bulk(_) | Header::Simple(_) | Header::Integer(_) => {}
+            _ => return Err(Error::ExpectedDollarSign),
+        }
+        match self.next_header()? {
+            Header::Bulk(Some(len)) => {
+                self.bulk_left = Some(len);
+                let mut buf = [0; 8192];
+                loop {
+                    let n = self.read_chunk(&mut buf)?;
+                    if n == 0 {
+                        return Ok(Some(len));
+                    }
+                    writer.write_all(&buf[..n])?;
+                }
+            }
+            Header::Simple(line) | Header::Integer(line) => {
+                writer.write_all(&line)?;
+                Ok(Some(line.len()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // 跳过还没有读取的分块内容
+    fn skip_chunks(&mut self) -> Result<()> {
+        let mut buf = [0; 8192];
+        while self.bulk_left.is_some() {
+            self.read_chunk(&mut buf)?;
+        }
+        Ok(())
+    }
+
+    // 分块内容读完之后检查结尾的 CRLF
+    fn finish_chunks(&mut self) -> Result<()> {
+        let mut end = [0; 2];
+        self.reader.read_exact(&mut end)?;
+        self.byte_offset += 2;
+        if let Some(raw) = &mut self.capture {
+            raw.extend_from_slice(&end);
+        }
+        check_bulk_end(&end)?;
+        self.bulk_left = None;
+        self.finish_element();
+        Ok(())
+    }
+
+    pub(crate) fn frames(&self) -> usize {
+        self.frames
+    }
+
+    // 跳过以 '#' 开头的注释行，例如 Redis 7 AOF 中的时间戳注解。
+    // 只能在两帧之间调用，返回 false 表示数据已经读完
+    pub(crate) fn skip_annotations(&mut self) -> Result<bool> {
+        loop {
+            match self.reader.fill_buf()?.first() {
+                None => return Ok(false),
+                Some(b'#') => {
+                    let mut buf = vec![];
+                    let n = self.reader.read_until(LF, &mut buf)?;
+                    self.byte_offset += n;
+                    if buf[n - 1] != LF {
+                        return Err(Error::Eof);
+                    }
+                }
+                Some(_) => return Ok(true),
+            }
+        }
+    }
+
+    // parser
+    fn next_char(&mut self) -> Result<u8> {
+        let ch = match self.reader.fill_buf()?.first() {
+            Some(&ch) => ch,
+            None => return Err(Error::Eof),
+        };
+        self.reader.consume(1);
+        self.byte_offset += 1;
+        if let Some(raw) = &mut self.capture {
+            raw.push(ch);
+        }
+        Ok(ch)
+    }
+
+    // 读取一行，去掉结尾的 CRLF
+    // TODO: 不要使用Vec<u8>，直接返回内部buffer的引用
+    fn next_line(&mut self) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        let limit = self.limits.max_line_len + 2;
+        let n = (&mut self.reader)
+            .take(limit as u64)
+            .read_until(LF, &mut buf)?;
+        self.byte_offset += n;
+        if let Some(raw) = &mut self.capture {
+            raw.extend_from_slice(&buf);
+        }
+        if n == 0 || buf[n - 1] != LF {
+            return Err(if n == limit {
+                Error::LimitExceeded("line length")
+            } else {
+                Error::Eof
+            });
+        }
+        let len = strip_crlf(&buf)?.len();
+        buf.truncate(len);
+        Ok(buf)
+    }
+
+    fn read_header(&mut self) -> Result<Header> {
+        let ch = self.next_char()?;
+        check_frame_type(ch)?;
+        let line = self.next_line()?;
+        let len = match check_header(ch, &line, &self.limits) {
+            // 整行已经读完，帧的边界仍然确定，这个元素算作完成，之后可以跳过这一帧剩下的部分
+            Err(Error::BadNumContent) if ch == b':' => {
+                self.finish_element();
+                return Err(Error::BadNumContent);
+            }
+            result => result?,
+        };
+        match ch {
+            b'*' => Ok(Header::Array(len)),
+            b'$' => Ok(Header::Bulk(len)),
+            b'+' => Ok(Header::Simple(line)),
+            b'-' => Ok(Header::Error(line)),
+            _ => Ok(Header::Integer(line)),
+        }
+    }
+
+    pub(crate) fn peek_header(&mut self) -> Result<&Header> {
+        if self.peeked.is_none() {
+            self.skip_chunks()?;
+            let mut buf = mem::take(&mut self.peeked_raw);
+            buf.clear();
+            let outer = self.capture.replace(buf);
+            let header = self.read_header();
+            self.peeked_raw = mem::replace(&mut self.capture, outer).unwrap_or_default();
+            if let Some(raw) = &mut self.capture {
+                raw.extend_from_slice(&self.peeked_raw);
+            }
+            self.peeked = Some(header?);
+        }
+        Ok(self.peeked.as_ref().unwrap())
+    }
+
+    // 取出头部，同时维护 pending。只有 bulk string 的数据还需要调用者继续读取
+    pub(crate) fn next_header(&mut self) -> Result<Header> {
+        let header = match self.peeked.take() {
+            Some(header) => header,
+            None => {
+                self.skip_chunks()?;
+                self.read_header()?
+            }
+        };
+        match header {
+            Header::Array(Some(len)) if len > 0 => {
+                self.limits.check_depth(self.pending.len())?;
+                self.pending.push(len)
+            }
+            Header::Bulk(Some(_)) => {}
+            _ => self.finish_element(),
+        }
+        Ok(header)
+    }
+
+    // 一个元素解析完成，更新所在数组的剩余个数。数组的最后一个元素完成时，数组本身也完成了
+    fn finish_element(&mut self) {
+        while let Some(left) = self.pending.last_mut() {
+            *left -= 1;
+            if *left > 0 {
+                return;
+            }
+            self.pending.pop();
+        }
+        self.frames += 1;
+    }
+
+    pub(crate) fn read_bulk_data(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0; len + 2];
+        self.reader.read_exact(&mut buf)?;
+        self.byte_offset += len + 2;
+        if let Some(raw) = &mut self.capture {
+            raw.extend_from_slice(&buf);
+        }
+        check_bulk_end(&buf[len..])?;
+        buf.truncate(len);
+        self.finish_element();
+        Ok(buf)
+    }
+
+    // 跳过一个完整的元素，如果是数组则跳过其中所有的元素
+    pub(crate) fn skip_element(&mut self) -> Result<()> {
+        let depth = self.pending.len();
+        loop {
+            if let Header::Bulk(Some(len)) = self.next_header()? {
+                self.read_bulk_data(len)?;
+            }
+            if self.pending.len() <= depth {
+                return Ok(());
+            }
+        }
+    }
+
+    // 读取下一个完整的元素，返回它的原始字节
+    pub(crate) fn read_raw(&mut self) -> Result<Vec<u8>> {
+        let raw = match self.peeked {
+            Some(_) => self.peeked_raw.clone(),
+            None => vec![],
+        };
+        self.capture = Some(raw);
+        let result = self.skip_element();
+        let raw = self.capture.take().unwrap_or_default();
+        result.map(|_| raw)
+    }
+
+    // 反序列化出错后，跳过当前帧还没有读取的部分。frames 是这一帧开始前完成的帧数，
+    // 用来判断出错时这一帧是否一个字节都还没有消耗
+    pub(crate) fn skip_frame_rest(&mut self, frames: usize) -> Result<()> {
+        if self.pending.is_empty() && self.frames == frames {
+            return self.skip_element();
+        }
+        while !self.pending.is_empty() {
+            self.skip_element()?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn check_frame_type(ch: u8) -> Result<()> {
+    match ch {
+        b'*' | b'$' | b'+' | b'-' | b':' => Ok(()),
+        _ => Err(Error::UnknownFrameType),
+    }
+}
+
+// 检查一行头部的类型符号和内容，返回数组或 bulk string 的长度，null 和其他类型是 None。
+// integer 的内容也在这里检查
+pub(crate) fn check_header(ch: u8, line: &[u8], limits: &Limits) -> Result<Option<usize>> {
+    let (len, max, what) = match ch {
+        b'*' => (
+            parse_length_hint(line)?,
+            limits.max_array_len,
+            "array length",
+        ),
+        b'$' => (
+            parse_length_hint(line)?,
+            limits.max_bulk_len,
+            "bulk string length",
+        ),
+        b'+' | b'-' => return Ok(None),
+        b':' => {
+            parse_integer(line)?;
+            return Ok(None);
+        }
+        _ => return Err(Error::UnknownFrameType),
+    };
+    if let Some(len) = len {
+        Limits::check(len, max, what)?;
+    }
+    Ok(len)
+}
+
+// 去掉以 LF 结尾的一行末尾的 CRLF
+pub(crate) fn strip_crlf(line: &[u8]) -> Result<&[u8]> {
+    let n = line.len();
+    if n < 2 || line[n - 2] != CR {
+        return Err(Error::UnbalancedCRLF);
+    }
+    Ok(&line[..n - 2])
+}
+
+// bulk string 数据之后的两个字节必须是 CRLF
+pub(crate) fn check_bulk_end(end: &[u8]) -> Result<()> {
+    if end[1] != LF {
+        return Err(Error::ExpectedLF);
+    }
+    if end[0] != CR {
+        return Err(Error::UnbalancedCRLF);
+    }
+    Ok(())
+}
+
+// 解析去掉类型符号和 CRLF 的长度，-1 表示 null
+fn parse_length_hint(line: &[u8]) -> Result<Option<usize>> {
+    if line == b"-1" {
+        return Ok(None);
+    }
+    if line.is_empty() {
+        return Err(Error::BadLengthHint);
+    }
+    let mut len: usize = 0;
+    for &ch in line {
+        match ch {
+            b'0'..=b'9' => {
+                len = len
+                    .checked_mul(10)
+                    .and_then(|l| l.checked_add(usize::from(ch - b'0')))
+                    .ok_or(Error::BadLengthHint)?;
+            }
+            _ => return Err(Error::BadLengthHint),
+        }
+    }
+    Ok(Some(len))
+}
+
+fn parse_integer(line: &[u8]) -> Result<i64> {
+    std::str::from_utf8(line)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or(Error::BadNumContent)
+}
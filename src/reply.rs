@@ -0,0 +1,214 @@
+use std::fmt::{self, Display};
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+// RedisError 序列化时使用的 newtype 名，ser::Serializer 据此写出 - 开头的错误回复，
+// 其它格式则只会看到错误的文本
+pub(crate) const ERROR_REPLY: &str = "$error";
+
+// 错误回复的第一个单词，Redis 约定用大写表示错误的种类
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    Err,
+    WrongType,
+    Moved,
+    Ask,
+    TryAgain,
+    CrossSlot,
+    ClusterDown,
+    NoScript,
+    Busy,
+    BusyKey,
+    Loading,
+    NoAuth,
+    WrongPass,
+    NoPerm,
+    ReadOnly,
+    Oom,
+    ExecAbort,
+    MasterDown,
+    Misconf,
+    // 其它大写开头的错误种类
+    Other(String),
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &str {
+        match *self {
+            ErrorCode::Err => "ERR",
+            ErrorCode::WrongType => "WRONGTYPE",
+            ErrorCode::Moved => "MOVED",
+            ErrorCode::Ask => "ASK",
+            ErrorCode::TryAgain => "TRYAGAIN",
+            ErrorCode::CrossSlot => "CROSSSLOT",
+            ErrorCode::ClusterDown => "CLUSTERDOWN",
+            ErrorCode::NoScript => "NOSCRIPT",
+            ErrorCode::Busy => "BUSY",
+            ErrorCode::BusyKey => "BUSYKEY",
+            ErrorCode::Loading => "LOADING",
+            ErrorCode::NoAuth => "NOAUTH",
+            ErrorCode::WrongPass => "WRONGPASS",
+            ErrorCode::NoPerm => "NOPERM",
+            ErrorCode::ReadOnly => "READONLY",
+            ErrorCode::Oom => "OOM",
+            ErrorCode::ExecAbort => "EXECABORT",
+            ErrorCode::MasterDown => "MASTERDOWN",
+            ErrorCode::Misconf => "MISCONF",
+            ErrorCode::Other(ref code) => code,
+        }
+    }
+
+    fn from_word(word: &str) -> ErrorCode {
+        match word {
+            "ERR" => ErrorCode::Err,
+            "WRONGTYPE" => ErrorCode::WrongType,
+            "MOVED" => ErrorCode::Moved,
+            "ASK" => ErrorCode::Ask,
+            "TRYAGAIN" => ErrorCode::TryAgain,
+            "CROSSSLOT" => ErrorCode::CrossSlot,
+            "CLUSTERDOWN" => ErrorCode::ClusterDown,
+            "NOSCRIPT" => ErrorCode::NoScript,
+            "BUSY" => ErrorCode::Busy,
+            "BUSYKEY" => ErrorCode::BusyKey,
+            "LOADING" => ErrorCode::Loading,
+            "NOAUTH" => ErrorCode::NoAuth,
+            "WRONGPASS" => ErrorCode::WrongPass,
+            "NOPERM" => ErrorCode::NoPerm,
+            "READONLY" => ErrorCode::ReadOnly,
+            "OOM" => ErrorCode::Oom,
+            "EXECABORT" => ErrorCode::ExecAbort,
+            "MASTERDOWN" => ErrorCode::MasterDown,
+            "MISCONF" => ErrorCode::Misconf,
+            other => ErrorCode::Other(other.to_owned()),
+        }
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
+// 服务端返回的错误回复，比如 -WRONGTYPE Operation against a key holding the wrong kind of value
+// 反序列化 Result<T, RedisError> 时，- 开头的回复得到 Err，其它回复交给 T
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedisError {
+    code: ErrorCode,
+    message: String,
+}
+
+impl RedisError {
+    pub fn new<M: Into<String>>(code: ErrorCode, message: M) -> Self {
+        RedisError {
+            code,
+            message: message.into(),
+        }
+    }
+
+    // 解析去掉 - 和 CRLF 之后的错误回复。第一个单词不是大写时，当作没有种类的 ERR
+    pub fn parse(line: &[u8]) -> Self {
+        let line = String::from_utf8_lossy(line);
+        let (word, message) = match line.find(' ') {
+            Some(i) => (&line[..i], &line[i + 1..]),
+            None => (&line[..], ""),
+        };
+        let is_code = !word.is_empty()
+            && word
+                .bytes()
+                .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'_');
+        if is_code {
+            RedisError::new(ErrorCode::from_word(word), message)
+        } else {
+            RedisError::new(ErrorCode::Err, line.into_owned())
+        }
+    }
+
+    pub fn code(&self) -> &ErrorCode {
+        &self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for RedisError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.message.is_empty() {
+            write!(formatter, "{}", self.code)
+        } else {
+            write!(formatter, "{} {}", self.code, self.message)
+        }
+    }
+}
+
+impl std::error::Error for RedisError {}
+
+impl Serialize for RedisError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(ERROR_REPLY, &self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for RedisError {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RedisErrorVisitor;
+
+        impl<'de> Visitor<'de> for RedisErrorVisitor {
+            type Value = RedisError;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("error reply")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<RedisError, E> {
+                Ok(RedisError::parse(v))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<RedisError, E> {
+                Ok(RedisError::parse(v.as_bytes()))
+            }
+        }
+
+        deserializer.deserialize_bytes(RedisErrorVisitor)
+    }
+}
+
+// 回复中的 simple string 和 integer，比如 COMMAND 回复中的 +readonly 和 :2。
+// 和 $error 一样用 newtype 名告诉 ser::Serializer，其它格式只看到里面的值
+pub(crate) const SIMPLE_REPLY: &str = "$simple";
+pub(crate) const INTEGER_REPLY: &str = "$integer";
+
+// 写成 + 开头的 simple string，内容不能包含 CR 和 LF
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Simple<T>(pub T);
+
+// 写成 : 开头的 integer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Integer(pub i64);
+
+impl<T: Serialize> Serialize for Simple<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(SIMPLE_REPLY, &self.0)
+    }
+}
+
+impl Serialize for Integer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(INTEGER_REPLY, &self.0)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Simple<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Simple)
+    }
+}
+
+impl<'de> Deserialize<'de> for Integer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        i64::deserialize(deserializer).map(Integer)
+    }
+}
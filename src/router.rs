@@ -0,0 +1,165 @@
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::command::{Command, RespCommand};
+use super::de::{from_reader, Deserializer};
+use super::error::{Error, Result};
+use super::info::{command_infos, CommandInfo, CommandTable};
+use super::reply::RedisError;
+use super::ser::{to_bytes, to_reply};
+
+// 处理一种命令类型，返回的 RedisError 会写成错误回复
+pub trait Handler<C> {
+    type Reply: Serialize;
+
+    fn handle(&mut self, cmd: C) -> std::result::Result<Self::Reply, RedisError>;
+}
+
+impl<C, R, F> Handler<C> for F
+where
+    R: Serialize,
+    F: FnMut(C) -> std::result::Result<R, RedisError>,
+{
+    type Reply = R;
+
+    fn handle(&mut self, cmd: C) -> std::result::Result<R, RedisError> {
+        self(cmd)
+    }
+}
+
+struct Route {
+    // 小写的命令名，多个单词已经切分好
+    names: Vec<Vec<String>>,
+    call: Box<dyn FnMut(Command) -> Result<Vec<u8>>>,
+}
+
+// 服务端的命令分发：按照命令名找到注册的命令类型，反序列化之后交给它的 Handler，
+// 再把回复序列化。未知命令、参数个数不对等错误会自动回复给客户端
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    table: CommandTable,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Router::default()
+    }
+
+    // 注册普通的 serde 命令类型，命令名从类型的结构中推导
+    pub fn route<C, H>(mut self, mut handler: H) -> Result<Self>
+    where
+        C: DeserializeOwned + 'static,
+        H: Handler<C> + 'static,
+    {
+        let infos = command_infos::<C>()?;
+        self.add(infos, move |cmd| {
+            // 命令已经读成了 Command，重新编码之后交给 C 匹配
+            let cmd: C = from_reader(&to_bytes(&cmd)?[..])?;
+            to_reply(&handler.handle(cmd))
+        });
+        Ok(self)
+    }
+
+    // 注册 #[derive(RespCommand)] 生成的命令类型
+    pub fn route_command<C, H>(mut self, mut handler: H) -> Self
+    where
+        C: RespCommand + 'static,
+        H: Handler<C> + 'static,
+    {
+        self.add(C::command_infos(), move |cmd| {
+            to_reply(&handler.handle(C::from_command(cmd)?))
+        });
+        self
+    }
+
+    fn add<F>(&mut self, infos: Vec<CommandInfo>, call: F)
+    where
+        F: FnMut(Command) -> Result<Vec<u8>> + 'static,
+    {
+        let names = infos
+            .iter()
+            .map(|info| info.name.split('|').map(str::to_owned).collect())
+            .collect();
+        self.routes.push(Route {
+            names,
+            call: Box::new(call),
+        });
+        self.table.extend(infos);
+    }
+
+    // 所有注册的命令，可以用来回复 COMMAND
+    pub fn table(&self) -> &CommandTable {
+        &self.table
+    }
+
+    // 处理一条命令，返回编码好的回复，出错时是错误回复
+    pub fn dispatch(&mut self, cmd: Command) -> Vec<u8> {
+        let route = match self.find(&cmd) {
+            Some(i) => &mut self.routes[i],
+            None => {
+                let name = String::from_utf8_lossy(&cmd.name).into_owned();
+                return error_reply(Error::UnknownCommand(name));
+            }
+        };
+        match (route.call)(cmd) {
+            Ok(reply) => reply,
+            Err(e) => error_reply(e),
+        }
+    }
+
+    // 命令名不区分大小写，多个单词的命令名优先匹配
+    fn find(&self, cmd: &Command) -> Option<usize> {
+        let matches = |name: &Vec<String>| {
+            let mut tokens = std::iter::once(&cmd.name).chain(&cmd.args);
+            name.len() <= cmd.args.len() + 1
+                && name
+                    .iter()
+                    .all(|t| t.as_bytes().eq_ignore_ascii_case(tokens.next().unwrap()))
+        };
+        self.routes
+            .iter()
+            .enumerate()
+            .flat_map(|(i, route)| route.names.iter().map(move |name| (i, name)))
+            .filter(|(_, name)| matches(name))
+            .max_by_key(|(_, name)| name.len())
+            .map(|(i, _)| i)
+    }
+
+    // 从 reader 中依次读取命令并把回复写入 writer，直到连接在两帧之间关闭。
+    // 数据流本身损坏时回复协议错误并返回
+    pub fn serve<R: Read, W: Write>(&mut self, reader: R, mut writer: W) -> Result<()> {
+        let mut de = Deserializer::from_reader(reader);
+        loop {
+            let start = de.bytes_offset();
+            let reply = match de.next_frame(PhantomData::<Command>) {
+                Ok(cmd) => self.dispatch(cmd),
+                // 下一帧还没有读到任何内容，是正常的结束
+                Err(Error::Eof) if de.bytes_offset() == start => return Ok(()),
+                Err(Error::Eof) => return Err(Error::Eof),
+                // 无法跳过这一帧剩下的部分时，同样不能再继续
+                Err(e) if e.is_fatal() || de.is_broken() => {
+                    let reply: std::result::Result<(), _> = Err(format!("Protocol error: {}", e));
+                    writer.write_all(&to_reply(&reply)?)?;
+                    writer.flush()?;
+                    return Err(e);
+                }
+                Err(e) => error_reply(e),
+            };
+            writer.write_all(&reply)?;
+            // 已经收到的请求都处理完之后才 flush，pipeline 的回复一起写出
+            if !de.has_buffered() {
+                writer.flush()?;
+            }
+        }
+    }
+}
+
+fn error_reply(e: Error) -> Vec<u8> {
+    let reply: std::result::Result<(), RedisError> = Err(e.into());
+    // 错误回复总是可以序列化
+    to_reply(&reply).unwrap_or_default()
+}
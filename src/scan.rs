@@ -0,0 +1,148 @@
+use std::io::{self, BufRead, Read, Write};
+
+use super::error::{Error, Result};
+use super::reader::{check_bulk_end, check_frame_type, check_header, strip_crlf, Limits, LF};
+
+// 只检查 resp 帧的结构，不构造任何值，适合代理、日志转发等只需要知道帧边界的场景
+
+// buf 开头是一个完整的帧时返回它的长度，数据还不完整时返回 None
+pub fn scan_frame(buf: &[u8]) -> Result<Option<usize>> {
+    scan_frame_with_limits(buf, &Limits::default())
+}
+
+pub fn scan_frame_with_limits(buf: &[u8], limits: &Limits) -> Result<Option<usize>> {
+    let mut frame = Frame::new(limits);
+    let mut pos = 0;
+    loop {
+        let ch = match buf.get(pos) {
+            Some(&ch) => ch,
+            None => return Ok(None),
+        };
+        check_frame_type(ch)?;
+        let rest = &buf[pos + 1..];
+        let window = &rest[..rest.len().min(limits.max_line_len + 2)];
+        let end = match window.iter().position(|&b| b == LF) {
+            Some(i) => i + 1,
+            None if window.len() == limits.max_line_len + 2 => {
+                return Err(Error::LimitExceeded("line length"))
+            }
+            None => return Ok(None),
+        };
+        let len = check_header(ch, strip_crlf(&window[..end])?, limits)?;
+        pos += 1 + end;
+        match (ch, len) {
+            (b'$', Some(len)) => {
+                if buf.len() - pos < len.saturating_add(2) {
+                    return Ok(None);
+                }
+                check_bulk_end(&buf[pos + len..pos + len + 2])?;
+                pos += len + 2;
+            }
+            (b'*', Some(len)) if len > 0 => {
+                frame.enter(len)?;
+                continue;
+            }
+            _ => {}
+        }
+        if frame.finish_element() {
+            return Ok(Some(pos));
+        }
+    }
+}
+
+// 从 reader 中读取一个完整的帧，原样写入 writer，返回它的长度。
+// 数据已经读完时返回 None，帧不完整时返回 Error::Eof
+pub fn copy_frame<R, W>(reader: &mut R, writer: &mut W, limits: &Limits) -> Result<Option<usize>>
+where
+    R: BufRead,
+    W: Write,
+{
+    let mut frame = Frame::new(limits);
+    let mut line = vec![];
+    let mut total = 0;
+    loop {
+        let ch = match reader.fill_buf()?.first() {
+            Some(&ch) => ch,
+            None if total == 0 => return Ok(None),
+            None => return Err(Error::Eof),
+        };
+        check_frame_type(ch)?;
+        reader.consume(1);
+        line.clear();
+        line.push(ch);
+        let limit = limits.max_line_len + 2;
+        let n = reader
+            .by_ref()
+            .take(limit as u64)
+            .read_until(LF, &mut line)?;
+        if n == 0 || line[n] != LF {
+            return Err(if n == limit {
+                Error::LimitExceeded("line length")
+            } else {
+                Error::Eof
+            });
+        }
+        let len = check_header(ch, strip_crlf(&line[1..])?, limits)?;
+        writer.write_all(&line)?;
+        total += 1 + n;
+        match (ch, len) {
+            (b'$', Some(len)) => {
+                let copied = io::copy(&mut reader.by_ref().take(len as u64), writer)?;
+                if copied < len as u64 {
+                    return Err(Error::Eof);
+                }
+                let mut end = [0; 2];
+                reader.read_exact(&mut end)?;
+                check_bulk_end(&end)?;
+                writer.write_all(&end)?;
+                total += len + 2;
+            }
+            (b'*', Some(len)) if len > 0 => {
+                frame.enter(len)?;
+                continue;
+            }
+            _ => {}
+        }
+        if frame.finish_element() {
+            return Ok(Some(total));
+        }
+    }
+}
+
+// 从 reader 中跳过一个完整的帧，返回它的长度
+pub fn skip_frame<R: BufRead>(reader: &mut R, limits: &Limits) -> Result<Option<usize>> {
+    copy_frame(reader, &mut io::sink(), limits)
+}
+
+// 每一层数组还没有读到的元素个数
+struct Frame<'a> {
+    pending: Vec<usize>,
+    limits: &'a Limits,
+}
+
+impl<'a> Frame<'a> {
+    fn new(limits: &'a Limits) -> Self {
+        Frame {
+            pending: vec![],
+            limits,
+        }
+    }
+
+    fn enter(&mut self, len: usize) -> Result<()> {
+        self.limits.check_depth(self.pending.len())?;
+        self.pending.push(len);
+        Ok(())
+    }
+
+    // 一个元素读完，返回整个帧是否已经读完
+    fn finish_element(&mut self) -> bool {
+        while let Some(left) = self.pending.last_mut() {
+            *left -= 1;
+            if *left > 0 {
+                return false;
+            }
+            self.pending.pop();
+        }
+        true
+    }
+}
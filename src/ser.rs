@@ -1,10 +1,26 @@
+use std::fmt::Display;
+use std::io::{self, Write};
+
 use serde::ser::{self, Serialize};
 
+use super::args::{collect_arg_list, collect_args};
+use super::command::CommandRef;
+use super::de::UNKNOWN_VARIANT;
 use super::error::{Error, Result};
+use super::key::KEY;
+use super::raw::RAW_FRAME;
+use super::reply::{ERROR_REPLY, INTEGER_REPLY, SIMPLE_REPLY};
+use super::writer::{VectoredWriter, Writer};
 
-pub struct Serializer {
+pub struct Serializer<W> {
     // 满足 redis protocol 的命令输出，以*开头
-    output: Vec<u8>,
+    output: Writer<W>,
+    // 正在序列化 $unknown variant，收集它的命令名和参数
+    unknown: Option<Vec<Vec<u8>>>,
+    // 刚刚进入 Some，还没有写入任何内容
+    in_some: bool,
+    // 正在写入 RawFrame，下一次 serialize_bytes 的内容原样写出
+    raw: bool,
 }
 
 // Redis Simple Protocol规定，发往服务端的信息，是bulk string，这里用bytes来表示
@@ -12,23 +28,171 @@ pub fn to_bytes<T>(value: &T) -> Result<Vec<u8>>
 where
     T: Serialize,
 {
-    let mut serializer = Serializer { output: vec![] };
+    let mut serializer = Serializer::new(vec![]);
     value.serialize(&mut serializer)?;
-    Ok(serializer.output)
+    Ok(serializer.into_inner())
+}
+
+// 直接写入 writer，出错时可能已经写入了一部分
+pub fn to_writer<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: io::Write,
+    T: ?Sized + Serialize,
+{
+    let mut serializer = Serializer::new(writer);
+    value.serialize(&mut serializer)
 }
 
-impl Serializer {
+// 同 to_writer，但是不复制很大的 bulk string，见 writer::VectoredWriter
+pub fn to_writer_vectored<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: io::Write,
+    T: ?Sized + Serialize,
+{
+    let mut serializer = Serializer::new(VectoredWriter::new(writer));
+    value.serialize(&mut serializer)?;
+    serializer.into_inner().into_inner()?;
+    Ok(())
+}
+
+// 写出一条命令，所有参数在同一次 write_vectored 中写出，不小于 threshold 的参数不复制。
+// to_writer_vectored 每遇到一个大的参数就写出一次，这里只有一次
+pub fn write_command_vectored<W: io::Write>(writer: W, cmd: &CommandRef<'_>) -> Result<()> {
+    let mut writer = VectoredWriter::new(writer);
+    write!(writer, "*{}\r\n", cmd.args.len() + 1)?;
+    for arg in Some(&cmd.name).into_iter().chain(&cmd.args) {
+        write!(writer, "${}\r\n", arg.len())?;
+        writer.write_borrowed(arg)?;
+        writer.write_all(b"\r\n")?;
+    }
+    writer.into_inner()?;
+    Ok(())
+}
+
+// 服务端的回复，Ok 的值正常序列化，Err 写成 - 开头的错误回复。
+// 错误信息的第一个单词不是大写的错误种类时，补上 ERR，比如 -ERR unknown command 'foo'
+pub fn to_reply<T, E>(reply: &std::result::Result<T, E>) -> Result<Vec<u8>>
+where
+    T: Serialize,
+    E: Display,
+{
+    match reply {
+        Ok(value) => to_bytes(value),
+        Err(e) => {
+            let mut serializer = Serializer::new(vec![]);
+            serializer.append_error(&e.to_string())?;
+            Ok(serializer.into_inner())
+        }
+    }
+}
+
+impl<W: io::Write> Serializer<W> {
+    pub fn new(writer: W) -> Self {
+        Serializer {
+            output: Writer::new(writer),
+            unknown: None,
+            in_some: false,
+            raw: false,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.output.into_inner()
+    }
+
     // Serializer添加bulk String的helper
-    fn append_element(&mut self, element: &[u8]) {
-        self.output
-            .extend_from_slice(&format!("${}\r\n", element.len()).as_bytes());
-        self.output.extend_from_slice(element);
-        self.output.push(b'\r');
-        self.output.push(b'\n');
+    fn append_element(&mut self, element: &[u8]) -> Result<()> {
+        self.in_some = false;
+        self.output.write_bulk(element)
+    }
+
+    // 写入命令的数组头和命令名。命令名中的空格把它分成多个 bulk string，
+    // 比如 #[serde(rename = "CLIENT SETNAME")] 会写成 CLIENT 和 SETNAME 两项
+    fn append_command(&mut self, name: &str, nargs: usize) -> Result<()> {
+        let tokens = name_tokens(name);
+        self.append_array_header(tokens.len() + nargs)?;
+        for token in tokens {
+            self.append_element(token.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    // $unknown variant 携带的就是原始的命令名和参数，展开后直接写成一条命令
+    fn append_args(&mut self, args: Vec<Vec<u8>>) -> Result<()> {
+        if args.is_empty() {
+            return Err(Error::Message("unknown command without name".to_owned()));
+        }
+        self.append_array_header(args.len())?;
+        for arg in args {
+            self.append_element(&arg)?;
+        }
+        Ok(())
+    }
+
+    fn append_error(&mut self, message: &str) -> Result<()> {
+        self.in_some = false;
+        self.output.write_error(message)
+    }
+
+    // simple string 和 integer 回复只有一行
+    fn append_line(&mut self, prefix: u8, line: &str) -> Result<()> {
+        self.output.write_line(prefix, line.as_bytes())?;
+        self.in_some = false;
+        Ok(())
+    }
+
+    fn append_array_header(&mut self, len: usize) -> Result<()> {
+        self.in_some = false;
+        self.output.write_array_header(len)
+    }
+
+    // Option 的编码：None 是 null bulk string，Some(v) 直接写 v。
+    // 但 v 本身也可能写成 null 时，比如 Some(None) 和 Some(())，为了和 None 区分，
+    // Some 写成只有一项的数组，即 Some(None) 是 *1\r\n$-1\r\n。
+    // 写入 None、Some、unit 之前调用，如果外面紧挨着一层 Some，先补上它的数组头
+    fn wrap_some(&mut self) -> Result<()> {
+        if self.in_some {
+            self.append_array_header(1)?;
+        }
+        Ok(())
+    }
+
+    fn finish_unknown(&mut self) -> Result<()> {
+        match self.unknown.take() {
+            Some(args) => self.append_args(args),
+            None => Ok(()),
+        }
     }
 }
 
-impl<'a> ser::Serializer for &'a mut Serializer {
+// $unknown 的 tuple 和 struct 形式，第一个字段是命令名，第二个字段是参数列表
+fn collect_unknown_field<T>(args: &mut Vec<Vec<u8>>, value: &T) -> Result<()>
+where
+    T: ?Sized + Serialize,
+{
+    if args.is_empty() {
+        args.push(collect_args(value)?.concat());
+    } else {
+        args.extend(collect_arg_list(value)?);
+    }
+    Ok(())
+}
+
+// 错误回复、simple string 和 integer 的内容，可以是任何序列化成字符串的类型
+fn line_text<T>(value: &T) -> Result<String>
+where
+    T: ?Sized + Serialize,
+{
+    let message = collect_args(value)?.join(&b' ');
+    Ok(String::from_utf8_lossy(&message).into_owned())
+}
+
+// 把命令名按空格切分，de 中匹配命令名时也使用同样的规则
+pub(crate) fn name_tokens(name: &str) -> Vec<&str> {
+    name.split(' ').filter(|t| !t.is_empty()).collect()
+}
+
+impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -42,8 +206,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
 
     // 首先从简单的方法开始。 以下12个方法，接受一个基本类型，映射为resp的一个bulk string
     fn serialize_bool(self, v: bool) -> Result<()> {
-        self.append_element(if v { b"true" } else { b"false" });
-        Ok(())
+        self.append_element(if v { b"true" } else { b"false" })
     }
 
     // resp的列表元素对整数类型不敏感
@@ -61,8 +224,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
 
     // 这里如果要追求性能，应该使用`itoa` crate，而不是to_string
     fn serialize_i64(self, v: i64) -> Result<()> {
-        self.append_element(&v.to_string().as_bytes());
-        Ok(())
+        self.append_element(v.to_string().as_bytes())
     }
 
     fn serialize_u8(self, v: u8) -> Result<()> {
@@ -78,8 +240,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_u64(self, v: u64) -> Result<()> {
-        self.append_element(&v.to_string().as_bytes());
-        Ok(())
+        self.append_element(v.to_string().as_bytes())
     }
 
     fn serialize_f32(self, _v: f32) -> Result<()> {
@@ -102,8 +263,11 @@ impl<'a> ser::Serializer for &'a mut Serializer {
 
     // bytes当作列表元素
     fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-        self.append_element(v);
-        Ok(())
+        if self.raw {
+            self.raw = false;
+            return self.output.write_raw(v);
+        }
+        self.append_element(v)
     }
 
     // 空值，null bulk string $-1\r\n表示
@@ -111,18 +275,21 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         self.serialize_unit()
     }
 
-    // Some(()) 也会被当作None处理，resp的表达能力还不够高
+    // Some(v) 直接写 v，v 也可能为 null 时见 wrap_some
     fn serialize_some<T>(self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
+        self.wrap_some()?;
+        self.in_some = true;
         value.serialize(self)
     }
 
     // 空值，null bulk string $-1\r\n表示
     fn serialize_unit(self) -> Result<()> {
-        self.output.extend_from_slice(b"$-1\r\n");
-        Ok(())
+        self.wrap_some()?;
+        self.in_some = false;
+        self.output.write_null()
     }
 
     // 列表的序列化，因为resp要求长度前置，所以如果集合没有长度就报错
@@ -133,8 +300,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
                 "length of sequence can't be determined".to_owned(),
             )),
             Some(l) => {
-                self.output
-                    .extend_from_slice(format!("*{}\r\n", l).as_bytes());
+                self.append_array_header(l)?;
                 Ok(self)
             }
         }
@@ -150,8 +316,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     // 对于struct，当成集合类型，把它处理一个单独的resp命令
     // 形如struct Foo; 可以看成无参数命令
     fn serialize_unit_struct(self, name: &'static str) -> Result<()> {
-        self.output.extend_from_slice(b"*1\r\n");
-        self.serialize_str(name)
+        self.append_command(name, 0)
     }
 
     // 官方鼓励 serializer 把 newtype structs 仅仅当作特定数据的简单包装，直接序列化
@@ -162,8 +327,28 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     where
         T: ?Sized + Serialize,
     {
-        self.output.extend_from_slice(b"*2\r\n");
-        self.serialize_str(name)?;
+        if name == ERROR_REPLY {
+            return self.append_error(&line_text(value)?);
+        }
+        if name == SIMPLE_REPLY {
+            return self.append_line(b'+', &line_text(value)?);
+        }
+        if name == INTEGER_REPLY {
+            return self.append_line(b':', &line_text(value)?);
+        }
+        // RawFrame 已经是完整的帧，原样写入。Some 中的帧也不加数组头，转发的回复保持不变
+        if name == RAW_FRAME {
+            self.in_some = false;
+            self.raw = true;
+            let result = value.serialize(&mut *self);
+            self.raw = false;
+            return result;
+        }
+        // key 只是一个标记，直接写入里面的值
+        if name == KEY {
+            return value.serialize(self);
+        }
+        self.append_command(name, 1)?;
         value.serialize(self)
     }
 
@@ -173,9 +358,8 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        let tuple = self.serialize_seq(Some(len + 1))?;
-        tuple.serialize_str(name)?;
-        Ok(tuple)
+        self.append_command(name, len)?;
+        Ok(self)
     }
 
     fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
@@ -198,7 +382,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     // 枚举 newtype struct， 所以直接扔给 newtype struct 处理
     fn serialize_newtype_variant<T>(
         self,
-        _name: &'static str,
+        name: &'static str,
         _variant_index: u32,
         variant: &'static str,
         value: &T,
@@ -206,6 +390,17 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     where
         T: ?Sized + Serialize,
     {
+        if variant == UNKNOWN_VARIANT {
+            return self.append_args(collect_arg_list(value)?);
+        }
+        // Result<T, E> 是服务端的回复，见 to_reply
+        if name == "Result" {
+            match variant {
+                "Ok" => return value.serialize(self),
+                "Err" => return self.append_error(&line_text(value)?),
+                _ => {}
+            }
+        }
         self.serialize_newtype_struct(variant, value)
     }
 
@@ -216,6 +411,10 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
+        if variant == UNKNOWN_VARIANT {
+            self.unknown = Some(vec![]);
+            return Ok(self);
+        }
         self.serialize_tuple_struct(variant, len)
     }
 
@@ -226,12 +425,16 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStructVariant> {
+        if variant == UNKNOWN_VARIANT {
+            self.unknown = Some(vec![]);
+            return Ok(self);
+        }
         self.serialize_tuple_struct(variant, len)
     }
 
     // Map 在 resp 中表示为多个命令。但是顺序无法保证
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        Err(Error::Message("float is not supported".to_owned()))
+        Err(Error::Message("map is not supported".to_owned()))
     }
 }
 
@@ -239,7 +442,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
 // element的序列化方法，最后以一个end结尾
 //
 // serialize_seq后返回当前实现
-impl<'a> ser::SerializeSeq for &'a mut Serializer {
+impl<'a, W: io::Write> ser::SerializeSeq for &'a mut Serializer<W> {
     // 和 the serializer 的Ok类型一致.
     type Ok = ();
     // 和 the serializer 的Error类型一致.
@@ -260,7 +463,7 @@ impl<'a> ser::SerializeSeq for &'a mut Serializer {
 }
 
 // tuples和seq一样
-impl<'a> ser::SerializeTuple for &'a mut Serializer {
+impl<'a, W: io::Write> ser::SerializeTuple for &'a mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -277,7 +480,7 @@ impl<'a> ser::SerializeTuple for &'a mut Serializer {
 }
 
 // 同上.
-impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
+impl<'a, W: io::Write> ser::SerializeTupleStruct for &'a mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -293,8 +496,8 @@ impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
     }
 }
 
-// 同上
-impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
+// 同上，$unknown 的字段需要先收集起来
+impl<'a, W: io::Write> ser::SerializeTupleVariant for &'a mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -302,16 +505,19 @@ impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        match self.unknown.as_mut() {
+            Some(args) => collect_unknown_field(args, value),
+            None => value.serialize(&mut **self),
+        }
     }
 
     fn end(self) -> Result<()> {
-        Ok(())
+        self.finish_unknown()
     }
 }
 
 // 把Struct的枚举当作Tuple, 忽略key，直接取数据，当作tuple
-impl<'a> ser::SerializeStruct for &'a mut Serializer {
+impl<'a, W: io::Write> ser::SerializeStruct for &'a mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -328,7 +534,7 @@ impl<'a> ser::SerializeStruct for &'a mut Serializer {
 }
 
 // 同上
-impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
+impl<'a, W: io::Write> ser::SerializeStructVariant for &'a mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -336,15 +542,18 @@ impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        match self.unknown.as_mut() {
+            Some(args) => collect_unknown_field(args, value),
+            None => value.serialize(&mut **self),
+        }
     }
 
     fn end(self) -> Result<()> {
-        Ok(())
+        self.finish_unknown()
     }
 }
 
-impl<'a> ser::SerializeMap for &'a mut Serializer {
+impl<'a, W: io::Write> ser::SerializeMap for &'a mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -0,0 +1,286 @@
+use std::collections::VecDeque;
+use std::io::{self, IoSlice};
+
+use super::error::{Error, Result};
+use super::reply::RedisError;
+
+// 逐个写入 resp 元素的底层接口，和 reader::Reader 对应，ser::Serializer 也建立在它之上。
+// 手写的热点路径可以直接使用，编码和检查都和 Serializer 相同。
+// 每个元素都会调用若干次 write_all，写入 socket 时最好包一层 BufWriter
+pub struct Writer<W> {
+    writer: W,
+}
+
+impl<W: io::Write> Writer<W> {
+    pub fn new(writer: W) -> Self {
+        Writer { writer }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    // 之后需要再写入 len 个元素
+    pub fn write_array_header(&mut self, len: usize) -> Result<()> {
+        write!(self.writer, "*{}\r\n", len)?;
+        Ok(())
+    }
+
+    pub fn write_bulk(&mut self, bytes: &[u8]) -> Result<()> {
+        write!(self.writer, "${}\r\n", bytes.len())?;
+        self.writer.write_all(bytes)?;
+        self.writer.write_all(b"\r\n")?;
+        Ok(())
+    }
+
+    // 从 reader 中读取 len 个字节作为 bulk string 的内容，不在内存中保存完整的内容。
+    // reader 提前结束时返回 Error::Eof，这时写出的数据已经不完整
+    pub fn write_bulk_from<R: io::Read>(&mut self, reader: R, len: usize) -> Result<()> {
+        write!(self.writer, "${}\r\n", len)?;
+        let copied = io::copy(&mut reader.take(len as u64), &mut self.writer)?;
+        if copied < len as u64 {
+            return Err(Error::Eof);
+        }
+        self.writer.write_all(b"\r\n")?;
+        Ok(())
+    }
+
+    pub fn write_integer(&mut self, n: i64) -> Result<()> {
+        write!(self.writer, ":{}\r\n", n)?;
+        Ok(())
+    }
+
+    // simple string 只有一行，不能包含 CR、LF
+    pub fn write_simple(&mut self, line: &[u8]) -> Result<()> {
+        self.write_line(b'+', line)
+    }
+
+    // 错误回复只有一行，其中的 CR、LF 替换成空格。
+    // 第一个单词不是大写的错误种类时，补上 ERR，比如 -ERR unknown command 'foo'
+    pub fn write_error(&mut self, message: &str) -> Result<()> {
+        let message = RedisError::parse(message.as_bytes()).to_string();
+        let line: Vec<u8> = message
+            .bytes()
+            .map(|b| if b == b'\r' || b == b'\n' { b' ' } else { b })
+            .collect();
+        self.write_line(b'-', &line)
+    }
+
+    // null bulk string，Option 的 None 和 unit 都写成它
+    pub fn write_null(&mut self) -> Result<()> {
+        self.writer.write_all(b"$-1\r\n")?;
+        Ok(())
+    }
+
+    pub fn write_null_array(&mut self) -> Result<()> {
+        self.writer.write_all(b"*-1\r\n")?;
+        Ok(())
+    }
+
+    // 已经编码好的完整的帧，比如 RawFrame，调用者保证它的格式
+    pub fn write_raw(&mut self, frame: &[u8]) -> Result<()> {
+        self.writer.write_all(frame)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    // 一行的回复。integer 回复的内容也可能来自任意类型序列化成的文本，见 ser::Serializer
+    pub(crate) fn write_line(&mut self, prefix: u8, line: &[u8]) -> Result<()> {
+        if line.iter().any(|&b| b == b'\r' || b == b'\n') {
+            return Err(Error::Message("line reply contains CR or LF".to_owned()));
+        }
+        self.writer.write_all(&[prefix])?;
+        self.writer.write_all(line)?;
+        self.writer.write_all(b"\r\n")?;
+        Ok(())
+    }
+}
+
+// 不小于这个长度的内容不复制，直接和缓冲区一起用 write_vectored 写出
+pub const DEFAULT_VECTORED_THRESHOLD: usize = 4096;
+// 缓冲区超过这个长度时先写出，避免大量的小参数占用过多内存
+const MAX_BUFFERED: usize = 64 * 1024;
+
+// 大块内容不复制的 io::Write。头部和小的参数先复制到缓冲区，不小于 threshold 的内容不复制：
+// io::Write::write 收到的大块内容马上和缓冲区一起用 write_vectored 写出；
+// write_borrowed 的内容在 'a 期间有效，先排进队列，之后和其他内容在同一次 write_vectored 中写出。
+// 用 Serializer::new(VectoredWriter::new(stream)) 序列化命令时，很大的 &[u8] 参数不会被复制到 Vec 中。
+// 和 BufWriter 一样，丢弃时写出剩下的内容并忽略错误，需要处理错误时调用 flush
+pub struct VectoredWriter<'a, W: io::Write> {
+    // 只有 into_inner 之后是 None
+    writer: Option<W>,
+    // 等待写出的内容，按照顺序
+    chunks: VecDeque<Chunk<'a>>,
+    // chunks 中复制过来的字节数
+    buffered: usize,
+    threshold: usize,
+}
+
+enum Chunk<'a> {
+    Owned(Vec<u8>),
+    Borrowed(&'a [u8]),
+}
+
+impl Chunk<'_> {
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Chunk::Owned(buf) => buf,
+            Chunk::Borrowed(data) => data,
+        }
+    }
+}
+
+impl<'a, W: io::Write> VectoredWriter<'a, W> {
+    pub fn new(writer: W) -> Self {
+        VectoredWriter {
+            writer: Some(writer),
+            chunks: VecDeque::new(),
+            buffered: 0,
+            threshold: DEFAULT_VECTORED_THRESHOLD,
+        }
+    }
+
+    pub fn set_threshold(&mut self, threshold: usize) {
+        self.threshold = threshold;
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.writer.as_ref().unwrap()
+    }
+
+    // 先写出剩下的内容
+    pub fn into_inner(mut self) -> Result<W> {
+        self.flush_chunks()?;
+        Ok(self.writer.take().unwrap())
+    }
+
+    // 不小于 threshold 的内容不复制，等到 flush 或者下一次写出大块内容时一起写出
+    pub fn write_borrowed(&mut self, data: &'a [u8]) -> io::Result<()> {
+        if data.len() < self.threshold {
+            self.buffer(data)
+        } else {
+            self.chunks.push_back(Chunk::Borrowed(data));
+            Ok(())
+        }
+    }
+
+    fn buffer(&mut self, data: &[u8]) -> io::Result<()> {
+        // 先写出再接受，返回错误时 data 没有被写入
+        if self.buffered + data.len() > MAX_BUFFERED {
+            self.flush_chunks()?;
+        }
+        match self.chunks.back_mut() {
+            Some(Chunk::Owned(buf)) => buf.extend_from_slice(data),
+            _ => self.chunks.push_back(Chunk::Owned(data.to_vec())),
+        }
+        self.buffered += data.len();
+        Ok(())
+    }
+
+    fn flush_chunks(&mut self) -> io::Result<()> {
+        self.write_chunks(&[]).1
+    }
+
+    // 用 write_vectored 写出队列中的内容和 extra，返回写出的 extra 的字节数。
+    // 出错时队列中去掉已经写出的部分
+    fn write_chunks(&mut self, extra: &[u8]) -> (usize, io::Result<()>) {
+        let queued: usize = self.chunks.iter().map(|c| c.as_bytes().len()).sum();
+        let writer = self.writer.as_mut().unwrap();
+        let mut slices: Vec<_> = self
+            .chunks
+            .iter()
+            .map(|c| IoSlice::new(c.as_bytes()))
+            .chain(Some(IoSlice::new(extra)).filter(|_| !extra.is_empty()))
+            .collect();
+        let mut slices = &mut slices[..];
+        // 去掉开头的空 slice
+        IoSlice::advance_slices(&mut slices, 0);
+        let mut written = 0;
+        let mut result = Ok(());
+        while !slices.is_empty() {
+            match writer.write_vectored(slices) {
+                Ok(0) => {
+                    result = Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write whole buffer",
+                    ));
+                    break;
+                }
+                Ok(n) => {
+                    written += n;
+                    IoSlice::advance_slices(&mut slices, n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        self.consume(written.min(queued));
+        (written.saturating_sub(queued), result)
+    }
+
+    // 从队列的开头去掉 n 个已经写出的字节
+    fn consume(&mut self, mut n: usize) {
+        while n > 0 {
+            let front = self.chunks.front_mut().unwrap();
+            let len = front.as_bytes().len();
+            if n >= len {
+                if let Chunk::Owned(_) = front {
+                    self.buffered -= len;
+                }
+                self.chunks.pop_front();
+                n -= len;
+                continue;
+            }
+            match front {
+                Chunk::Owned(buf) => {
+                    buf.drain(..n);
+                    self.buffered -= n;
+                }
+                Chunk::Borrowed(data) => *data = &data[n..],
+            }
+            n = 0;
+        }
+    }
+}
+
+impl<W: io::Write> io::Write for VectoredWriter<'_, W> {
+    // 返回 Ok(n) 时 data 的前 n 个字节已经写出或者放进了队列；返回错误时 data 没有被写入
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if data.len() < self.threshold {
+            self.buffer(data)?;
+            return Ok(data.len());
+        }
+        match self.write_chunks(data) {
+            (0, Err(e)) => Err(e),
+            (n, _) => Ok(n),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_chunks()?;
+        self.writer.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: io::Write> Drop for VectoredWriter<'_, W> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.flush_chunks();
+        }
+    }
+}
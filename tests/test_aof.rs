@@ -0,0 +1,301 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::ser::{Error as _, Serialize, Serializer};
+use serde_resp::aof::{
+    self, AofFile, AofFileType, AofReader, AofWriter, Fsync, Manifest, MultiPartReader,
+};
+use serde_resp::{Command, Error};
+
+const AOF: &[u8] = b"*2\r\n$6\r\nSELECT\r\n$1\r\n0\r\n\
+    #TS:1680000000\r\n\
+    *3\r\n$3\r\nSET\r\n$1\r\nk\r\n$5\r\nhello\r\n\
+    *2\r\n$4\r\nINCR\r\n$1\r\nn\r\n";
+
+#[test]
+fn test_read_entries() {
+    let entries: Vec<_> = AofReader::new(AOF).map(Result::unwrap).collect();
+    let offsets: Vec<_> = entries.iter().map(|e| e.offset).collect();
+    assert_eq!(offsets, vec![0, 39, 70]);
+    assert_eq!(
+        entries[1].command,
+        Command::new("SET").arg("k").arg("hello")
+    );
+    assert_eq!(entries[2].command, Command::new("INCR").arg("n"));
+
+    let mut reader = AofReader::new(&b""[..]);
+    assert!(reader.next().is_none());
+    assert_eq!(reader.valid_len(), 0);
+}
+
+#[test]
+fn test_truncated_tail() {
+    // 每一个前缀都是从最后一条完整命令之后截断的
+    let ends = [0, 23, 39, 70, AOF.len()];
+    for len in 0..AOF.len() {
+        let valid = *ends.iter().filter(|&&end| end <= len).max().unwrap();
+        let mut reader = AofReader::new(&AOF[..len]);
+        let result: Result<Vec<_>, _> = reader.by_ref().collect();
+        match result {
+            Ok(_) => assert!(ends.contains(&len), "len {}", len),
+            Err(Error::Truncated(offset)) => assert_eq!(offset, valid, "len {}", len),
+            Err(e) => panic!("len {}: {}", len, e),
+        }
+        assert_eq!(reader.valid_len(), valid);
+    }
+}
+
+#[test]
+fn test_corrupted() {
+    let mut data = AOF.to_vec();
+    data[39] = b'?';
+    let mut reader = AofReader::new(&data[..]);
+    assert!(reader.next().unwrap().is_ok());
+    match reader.next() {
+        Some(Err(Error::UnknownFrameType)) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(reader.next().is_none());
+    assert_eq!(reader.valid_len(), 39);
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("serde_resp_{}_{}.aof", name, std::process::id()))
+}
+
+#[test]
+fn test_fix() {
+    let path = temp_path("fix");
+
+    fs::write(&path, AOF).unwrap();
+    assert_eq!(aof::fix(&path).unwrap(), None);
+    assert_eq!(fs::read(&path).unwrap(), AOF);
+
+    fs::write(&path, &AOF[..AOF.len() - 3]).unwrap();
+    assert_eq!(aof::fix(&path).unwrap(), Some(70));
+    assert_eq!(fs::read(&path).unwrap(), &AOF[..70]);
+    assert_eq!(AofReader::open(&path).unwrap().count(), 2);
+
+    // 中间损坏的文件不会被截断
+    let mut data = AOF.to_vec();
+    data[39] = b'?';
+    fs::write(&path, &data).unwrap();
+    assert!(aof::fix(&path).is_err());
+    assert_eq!(fs::read(&path).unwrap(), data);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_writer() {
+    let path = temp_path("writer");
+    let _ = fs::remove_file(&path);
+    for &fsync in &[Fsync::Always, Fsync::EverySec, Fsync::No] {
+        let mut writer = AofWriter::open(&path, fsync).unwrap();
+        writer.append(&Command::new("INCR").arg("n")).unwrap();
+        writer.tick().unwrap();
+    }
+    // 重新打开时在结尾追加
+    let mut writer = AofWriter::open(&path, Fsync::Always).unwrap();
+    writer
+        .append(&Command::new("SET").arg("k").arg("v"))
+        .unwrap();
+    let commands: Vec<_> = AofReader::open(&path)
+        .unwrap()
+        .map(|e| e.unwrap().command)
+        .collect();
+    assert_eq!(commands.len(), 4);
+    assert_eq!(commands[3], Command::new("SET").arg("k").arg("v"));
+    fs::remove_file(&path).unwrap();
+}
+
+struct Broken;
+
+impl Serialize for Broken {
+    fn serialize<S: Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+        Err(S::Error::custom("broken"))
+    }
+}
+
+#[test]
+fn test_rewrite() {
+    let path = temp_path("rewrite");
+    let _ = fs::remove_file(&path);
+    let mut writer = AofWriter::open(&path, Fsync::EverySec).unwrap();
+    for _ in 0..10 {
+        writer.append(&Command::new("INCR").arg("n")).unwrap();
+    }
+    writer
+        .rewrite(vec![Command::new("SET").arg("n").arg("10")])
+        .unwrap();
+    // 重写之后继续追加到新的文件
+    writer.append(&Command::new("INCR").arg("n")).unwrap();
+    let commands: Vec<_> = AofReader::open(&path)
+        .unwrap()
+        .map(|e| e.unwrap().command)
+        .collect();
+    assert_eq!(
+        commands,
+        vec![
+            Command::new("SET").arg("n").arg("10"),
+            Command::new("INCR").arg("n")
+        ]
+    );
+
+    // 快照写入失败时原来的文件不变，也不留下临时文件
+    let before = fs::read(&path).unwrap();
+    assert!(writer.rewrite(vec![Broken]).is_err());
+    assert_eq!(fs::read(&path).unwrap(), before);
+    let mut temp = path.clone().into_os_string();
+    temp.push(".tmp");
+    assert!(!PathBuf::from(temp).exists());
+    writer.append(&Command::new("INCR").arg("n")).unwrap();
+    assert_eq!(AofReader::open(&path).unwrap().count(), 3);
+    fs::remove_file(&path).unwrap();
+}
+
+const MANIFEST: &str = "file appendonly.aof.1.base.rdb seq 1 type b\n\
+    file appendonly.aof.1.incr.aof seq 1 type h\n\
+    file appendonly.aof.2.incr.aof seq 2 type i\n\
+    file \"appendonly aof.3.incr.aof\" seq 3 type i\n";
+
+#[test]
+fn test_manifest() {
+    let text = format!(
+        "# comment\n\n{}",
+        MANIFEST.replace(" type h", " type h extra 1")
+    );
+    let manifest: Manifest = text.parse().unwrap();
+    assert_eq!(manifest.files.len(), 4);
+    assert!(manifest.base().unwrap().is_rdb());
+    assert_eq!(
+        manifest.files[3],
+        AofFile {
+            name: "appendonly aof.3.incr.aof".to_owned(),
+            seq: 3,
+            kind: AofFileType::Incr,
+        }
+    );
+    let seqs: Vec<_> = manifest.incrs().map(|f| f.seq).collect();
+    assert_eq!(seqs, vec![2, 3]);
+    manifest.validate().unwrap();
+    assert_eq!(manifest.to_string(), MANIFEST);
+
+    // 需要转义的文件名
+    let mut escaped = manifest.clone();
+    escaped.files[0].name = "a\"b\\c\nd\u{1}".to_owned();
+    let text = escaped.to_string();
+    assert!(text.starts_with("file \"a\\\"b\\\\c\\nd\\x01\" seq 1"));
+    assert_eq!(text.parse::<Manifest>().unwrap(), escaped);
+    let single: Manifest = "file 'it\\'s' seq 1 type b".parse().unwrap();
+    assert_eq!(single.files[0].name, "it's");
+
+    for bad in &[
+        "file a seq 1",
+        "file a seq x type b",
+        "file a seq 1 type z",
+        "file \"a seq 1 type b",
+        "file \"a\"b seq 1 type b",
+        "name a seq 1 type b",
+    ] {
+        assert!(
+            matches!(bad.parse::<Manifest>(), Err(Error::BadManifest(_))),
+            "{}",
+            bad
+        );
+    }
+
+    for bad in &[
+        "",
+        "file a seq 1 type h",
+        "file a seq 1 type b\nfile b seq 2 type b",
+        "file a seq 1 type i\nfile a seq 2 type i",
+        "file a seq 2 type i\nfile b seq 2 type i",
+        "file ../a seq 1 type b",
+    ] {
+        let manifest: Manifest = bad.parse().unwrap();
+        assert!(
+            matches!(manifest.validate(), Err(Error::BadManifest(_))),
+            "{}",
+            bad
+        );
+    }
+}
+
+#[test]
+fn test_multi_part() {
+    let dir = std::env::temp_dir().join(format!("serde_resp_dir_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    let manifest = Manifest {
+        files: vec![
+            AofFile {
+                name: "appendonly.aof.1.base.aof".to_owned(),
+                seq: 1,
+                kind: AofFileType::Base,
+            },
+            AofFile {
+                name: "appendonly.aof.1.incr.aof".to_owned(),
+                seq: 1,
+                kind: AofFileType::Incr,
+            },
+            AofFile {
+                name: "appendonly.aof.2.incr.aof".to_owned(),
+                seq: 2,
+                kind: AofFileType::Incr,
+            },
+        ],
+    };
+    manifest.save(dir.join("appendonly.aof.manifest")).unwrap();
+    fs::write(dir.join("appendonly.aof.1.base.aof"), AOF).unwrap();
+    fs::write(dir.join("appendonly.aof.1.incr.aof"), b"").unwrap();
+    fs::write(
+        dir.join("appendonly.aof.2.incr.aof"),
+        b"*2\r\n$3\r\nDEL\r\n$1\r\nk\r\n*1\r\n$4\r\nPI",
+    )
+    .unwrap();
+
+    let mut reader = MultiPartReader::open(&dir).unwrap();
+    let entries: Vec<_> = reader.by_ref().collect();
+    assert_eq!(entries.len(), 5);
+    assert_eq!(
+        entries[3].as_ref().unwrap().command,
+        Command::new("DEL").arg("k")
+    );
+    assert!(matches!(entries[4], Err(Error::Truncated(20))));
+    assert_eq!(reader.current_file().unwrap().seq, 2);
+
+    // 不是最后一个文件时，不完整的结尾是错误
+    let mut manifest = manifest;
+    manifest.files.swap(1, 2);
+    manifest.files[1].seq = 1;
+    manifest.files[2].seq = 2;
+    let mut reader = MultiPartReader::new(&dir, &manifest).unwrap();
+    let entries: Vec<_> = reader.by_ref().collect();
+    assert_eq!(entries.len(), 5);
+    match &entries[4] {
+        Err(Error::Message(msg)) => {
+            assert_eq!(msg, "appendonly.aof.2.incr.aof is truncated at offset 20")
+        }
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(
+        reader.current_file().unwrap().name,
+        "appendonly.aof.2.incr.aof"
+    );
+
+    // RDB 格式的 base 文件默认是错误，需要明确选择跳过
+    manifest.files[0].name = "appendonly.aof.1.base.rdb".to_owned();
+    manifest.files.truncate(2);
+    let entries: Vec<_> = MultiPartReader::new(&dir, &manifest).unwrap().collect();
+    assert_eq!(entries.len(), 1);
+    assert!(matches!(&entries[0], Err(Error::Message(_))));
+    let mut reader = MultiPartReader::new(&dir, &manifest).unwrap();
+    reader.set_skip_rdb_base(true);
+    let entries: Vec<_> = reader.collect();
+    assert_eq!(entries.len(), 2);
+    assert!(matches!(entries[1], Err(Error::Truncated(20))));
+
+    fs::write(dir.join("other.manifest"), b"").unwrap();
+    assert!(MultiPartReader::open(&dir).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+use serde_resp::router::Router;
+use serde_resp::{Client, Error, ErrorCode, RedisError, Simple};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "GET")]
+struct Get(String);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "SET")]
+struct Set(String, String);
+
+#[derive(Serialize)]
+#[serde(rename = "FOO")]
+struct Foo;
+
+// 在本地启动一个只处理 GET 和 SET 的服务端
+fn mock_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let store = Arc::new(Mutex::new(HashMap::new()));
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            let (get_store, set_store) = (store.clone(), store.clone());
+            thread::spawn(move || {
+                let mut router = Router::new()
+                    .route(move |Get(key)| {
+                        Ok::<_, RedisError>(get_store.lock().unwrap().get(&key).cloned())
+                    })
+                    .unwrap()
+                    .route(move |Set(key, value)| {
+                        set_store.lock().unwrap().insert(key, value);
+                        Ok(Simple("OK"))
+                    })
+                    .unwrap();
+                let _ = router.serve(stream.try_clone().unwrap(), stream);
+            });
+        }
+    });
+    addr
+}
+
+#[test]
+fn test_query() {
+    let mut client = Client::connect(mock_server()).unwrap();
+    let reply: String = client.query(&Set("k".into(), "v".into())).unwrap();
+    assert_eq!(reply, "OK");
+    let reply: Option<String> = client.query(&Get("k".into())).unwrap();
+    assert_eq!(reply, Some("v".to_owned()));
+    let reply: Option<String> = client.query(&Get("none".into())).unwrap();
+    assert_eq!(reply, None);
+
+    // 错误回复
+    match client.query::<_, String>(&Foo) {
+        Err(Error::Redis(e)) => {
+            assert_eq!(e.code(), &ErrorCode::Err);
+            assert_eq!(e.to_string(), "ERR unknown command 'FOO'");
+        }
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+    let reply: Result<String, RedisError> = client.query(&Foo).unwrap();
+    assert!(reply.is_err());
+
+    // 出错之后连接仍然可用
+    let reply: Option<String> = client.query(&Get("k".into())).unwrap();
+    assert_eq!(reply, Some("v".to_owned()));
+}
+
+#[test]
+fn test_stream_bulk() {
+    let mut client = Client::connect(mock_server()).unwrap();
+    client
+        .send_with(|w| {
+            w.write_array_header(3)?;
+            w.write_bulk(b"SET")?;
+            w.write_bulk(b"big")?;
+            w.write_bulk_from(io::repeat(b'x'), 300_000)
+        })
+        .unwrap();
+    assert_eq!(client.recv::<String>().unwrap(), "OK");
+
+    let mut output = vec![];
+    client.send(&Get("big".into())).unwrap();
+    assert_eq!(client.recv_bulk_to(&mut output).unwrap(), Some(300_000));
+    assert!(output.len() == 300_000 && output.iter().all(|&b| b == b'x'));
+    client.send(&Get("none".into())).unwrap();
+    assert_eq!(client.recv_bulk_to(&mut output).unwrap(), None);
+    client.send(&Foo).unwrap();
+    assert!(matches!(
+        client.recv_bulk_to(&mut output),
+        Err(Error::Redis(_))
+    ));
+    assert_eq!(client.pending_replies(), 0);
+
+    // 命令只写了一部分，连接不能继续使用
+    let result = client.send_with(|w| {
+        w.write_array_header(3)?;
+        w.write_bulk(b"SET")?;
+        w.write_bulk(b"k")?;
+        w.write_bulk_from(&b"abc"[..], 10)
+    });
+    assert!(matches!(result, Err(Error::Eof)));
+    assert!(client.is_broken());
+    assert!(client.query::<_, String>(&Get("big".into())).is_err());
+}
+
+#[test]
+fn test_pipeline() {
+    let mut client = Client::connect(mock_server()).unwrap();
+    let (ok, value, none): (String, Option<String>, Option<String>) = client
+        .pipeline()
+        .cmd(&Set("a".into(), "1".into()))
+        .unwrap()
+        .cmd(&Get("a".into()))
+        .unwrap()
+        .cmd(&Get("b".into()))
+        .unwrap()
+        .query()
+        .unwrap();
+    assert_eq!(
+        (ok.as_str(), value, none),
+        ("OK", Some("1".to_owned()), None)
+    );
+
+    // 中间的错误回复，剩下的回复也会被读掉
+    let mut pipeline = client.pipeline();
+    pipeline.cmd(&Get("a".into())).unwrap();
+    pipeline.cmd(&Foo).unwrap();
+    pipeline.cmd(&Get("a".into())).unwrap();
+    let result = pipeline.query::<Vec<Option<String>>>();
+    assert!(matches!(result, Err(Error::Redis(_))));
+
+    let mut pipeline = client.pipeline();
+    pipeline.cmd(&Get("a".into())).unwrap();
+    pipeline.cmd(&Foo).unwrap();
+    let replies: Vec<Result<Option<String>, RedisError>> = pipeline.query().unwrap();
+    assert_eq!(replies[0], Ok(Some("1".to_owned())));
+    assert!(replies[1].is_err());
+
+    let reply: Option<String> = client.query(&Get("a".into())).unwrap();
+    assert_eq!(reply, Some("1".to_owned()));
+}
+
+#[test]
+fn test_buffered_replies() {
+    // 服务端一次写回两个回复，第二个回复在客户端的缓冲区中
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0; 64];
+        let _ = stream.read(&mut buf).unwrap();
+        stream.write_all(b"+OK\r\n$1\r\nv\r\n").unwrap();
+        // 之后的数据损坏
+        let _ = stream.read(&mut buf).unwrap();
+        stream.write_all(b"?\r\n").unwrap();
+    });
+
+    let mut client = Client::connect(addr).unwrap();
+    client.send(&Set("k".into(), "v".into())).unwrap();
+    let ok: String = client.recv().unwrap();
+    assert_eq!(ok, "OK");
+    let value: String = client.recv().unwrap();
+    assert_eq!(value, "v");
+
+    assert!(client.query::<_, String>(&Get("k".into())).is_err());
+    // 连接已经损坏
+    assert!(matches!(
+        client.query::<_, String>(&Get("k".into())),
+        Err(Error::Io(_))
+    ));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unix_stream() {
+    use std::os::unix::net::UnixListener;
+
+    let path = std::env::temp_dir().join(format!("serde_resp_{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut router = Router::new()
+            .route(|Get(key)| Ok::<_, RedisError>(key))
+            .unwrap();
+        let _ = router.serve(stream.try_clone().unwrap(), stream);
+    });
+
+    let mut client = Client::connect_unix(&path).unwrap();
+    let reply: String = client.query(&Get("echo".into())).unwrap();
+    assert_eq!(reply, "echo");
+    let _ = std::fs::remove_file(&path);
+}
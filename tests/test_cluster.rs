@@ -0,0 +1,107 @@
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use serde_resp::cluster::{command_slot, key_slot, KeyPosition, Redirect, RedirectKind};
+use serde_resp::{de, from_reader, to_bytes, Command, ErrorCode, RedisError};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+enum Request {
+    #[serde(rename = "GET")]
+    Get(String),
+    #[serde(rename = "PING")]
+    Ping,
+    #[serde(rename = "OBJECT ENCODING")]
+    ObjectEncoding(String),
+}
+
+impl KeyPosition for Request {
+    fn first_key(&self) -> Option<usize> {
+        match self {
+            Request::Get(_) => Some(1),
+            Request::Ping => None,
+            Request::ObjectEncoding(_) => Some(2),
+        }
+    }
+}
+
+#[test]
+fn test_key_slot() {
+    assert_eq!(key_slot(b"123456789"), 12739);
+    assert_eq!(key_slot(b"foo"), 12182);
+    assert_eq!(key_slot(b""), 0);
+    assert_eq!(
+        key_slot(b"{user1000}.following"),
+        key_slot(b"{user1000}.followers")
+    );
+    assert_eq!(key_slot(b"{user1000}.following"), key_slot(b"user1000"));
+    // 空的 hashtag 不生效，只取第一对大括号
+    assert_eq!(key_slot(b"foo{}{bar}"), 8363);
+    assert_eq!(key_slot(b"foo{{bar}}zap"), key_slot(b"{bar"));
+    assert_eq!(key_slot(b"foo{bar}{zap}"), key_slot(b"bar"));
+}
+
+#[test]
+fn test_command_slot() {
+    let get = Request::Get("foo".to_owned());
+    assert_eq!(command_slot(&get).unwrap(), Some(12182));
+    assert_eq!(command_slot(&Request::Ping).unwrap(), None);
+    let enc = Request::ObjectEncoding("foo".to_owned());
+    assert_eq!(command_slot(&enc).unwrap(), Some(12182));
+    let cmd = Command::new("SET").arg("{foo}bar").arg("v");
+    assert_eq!(command_slot(&cmd).unwrap(), Some(12182));
+}
+
+#[test]
+fn test_parse_redirect() {
+    let moved = RedisError::parse(b"MOVED 3999 127.0.0.1:6381");
+    assert_eq!(
+        Redirect::from_error(&moved),
+        Some(Redirect {
+            kind: RedirectKind::Moved,
+            slot: 3999,
+            addr: "127.0.0.1:6381".to_owned(),
+        })
+    );
+    let ask = Redirect::from_error(&RedisError::parse(b"ASK 1 [::1]:7000")).unwrap();
+    assert_eq!(ask.kind, RedirectKind::Ask);
+    assert_eq!(ask.addr, "[::1]:7000");
+    assert_eq!(RedisError::from(ask).code(), &ErrorCode::Ask);
+
+    assert!(Redirect::from_error(&RedisError::parse(b"ERR MOVED 1 a:1")).is_none());
+    assert!(Redirect::from_error(&RedisError::parse(b"MOVED 16384 a:1")).is_none());
+    assert!(Redirect::from_error(&RedisError::parse(b"MOVED 1")).is_none());
+}
+
+#[test]
+fn test_mock_redirect() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    // 模拟一个不负责任何 slot 的节点，对每个请求都返回 MOVED
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let reader = stream.try_clone().unwrap();
+        for req in de::Deserializer::from_reader(reader).into_iter::<Request>() {
+            let slot = command_slot(&req.unwrap()).unwrap().unwrap();
+            let err = RedisError::from(Redirect {
+                kind: RedirectKind::Moved,
+                slot,
+                addr: "127.0.0.1:6381".to_owned(),
+            });
+            let reply: Result<String, RedisError> = Err(err);
+            stream.write_all(&to_bytes(&reply).unwrap()).unwrap();
+        }
+    });
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let get = Request::Get("{user1000}.following".to_owned());
+    stream.write_all(&to_bytes(&get).unwrap()).unwrap();
+    let reply: Result<String, RedisError> = from_reader(&stream).unwrap();
+    let redirect = Redirect::from_error(&reply.unwrap_err()).unwrap();
+    assert_eq!(redirect.kind, RedirectKind::Moved);
+    assert_eq!(redirect.slot, key_slot(b"user1000"));
+    assert_eq!(redirect.addr, "127.0.0.1:6381");
+
+    drop(stream);
+    server.join().unwrap();
+}
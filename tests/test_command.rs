@@ -0,0 +1,51 @@
+use serde_resp::{from_reader, to_bytes, Command, CommandRef};
+
+#[test]
+fn test_builder() {
+    let cmd = Command::new("SET").arg("key").arg(42).arg(b"\r\n".to_vec());
+    assert_eq!(
+        to_bytes(&cmd).unwrap(),
+        b"*4\r\n$3\r\nSET\r\n$3\r\nkey\r\n$2\r\n42\r\n$2\r\n\r\n\r\n"
+    );
+    assert_eq!(to_bytes(&cmd.borrowed()).unwrap(), to_bytes(&cmd).unwrap());
+
+    let value = b"value".to_vec();
+    let cmd_ref = CommandRef::new(b"APPEND").arg(b"key").arg(&value);
+    assert_eq!(
+        to_bytes(&cmd_ref).unwrap(),
+        b"*3\r\n$6\r\nAPPEND\r\n$3\r\nkey\r\n$5\r\nvalue\r\n"
+    );
+    assert_eq!(
+        cmd_ref.into_owned(),
+        Command::new("APPEND").arg("key").arg("value")
+    );
+}
+
+#[test]
+fn test_round_trip() {
+    let cmd = Command::new("MODULE.CMD").arg("a").arg("").arg(-1);
+    let bytes = to_bytes(&cmd).unwrap();
+    assert_eq!(cmd, from_reader::<_, Command>(&bytes[..]).unwrap());
+
+    let cmd: Command = from_reader(&b"*1\r\n$4\r\nPING\r\n"[..]).unwrap();
+    assert_eq!(cmd, Command::new("PING"));
+    assert!(from_reader::<_, Command>(&b"*0\r\n"[..]).is_err());
+}
+
+#[test]
+fn test_unknown_fallback() {
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    enum Request {
+        Get(String),
+        #[serde(rename = "$unknown")]
+        Unknown(Command),
+    }
+
+    let bytes = b"*3\r\n$6\r\nHSETNX\r\n$1\r\nh\r\n$1\r\nf\r\n";
+    let req: Request = from_reader(&bytes[..]).unwrap();
+    assert_eq!(
+        req,
+        Request::Unknown(Command::new("HSETNX").arg("h").arg("f"))
+    );
+    assert_eq!(to_bytes(&req).unwrap(), bytes);
+}
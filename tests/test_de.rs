@@ -137,3 +137,209 @@ fn test_char() {
         '🌟'
     );
 }
+
+#[test]
+fn test_multi_word_name() {
+    #[derive(serde::Deserialize, PartialEq, Debug)]
+    enum Test {
+        #[serde(rename = "CLIENT SETNAME")]
+        ClientSetname(String),
+        #[serde(rename = "CLIENT GETNAME")]
+        ClientGetname,
+        #[serde(rename = "CONFIG GET")]
+        ConfigGet { parameter: String },
+    }
+
+    assert_eq!(
+        Test::ClientSetname("conn".to_owned()),
+        from_reader(R!(b"*3\r\n$6\r\nCLIENT\r\n$7\r\nSETNAME\r\n$4\r\nconn\r\n")).unwrap()
+    );
+    assert_eq!(
+        Test::ClientGetname,
+        from_reader(R!(b"*2\r\n$6\r\nCLIENT\r\n$7\r\nGETNAME\r\n")).unwrap()
+    );
+    assert_eq!(
+        Test::ConfigGet {
+            parameter: "save".to_owned()
+        },
+        from_reader(R!(b"*3\r\n$6\r\nCONFIG\r\n$3\r\nGET\r\n$4\r\nsave\r\n")).unwrap()
+    );
+
+    #[derive(serde::Deserialize, PartialEq, Debug)]
+    #[serde(rename = "CLUSTER INFO")]
+    struct ClusterInfo;
+    assert_eq!(
+        ClusterInfo,
+        from_reader(R!(b"*2\r\n$7\r\nCLUSTER\r\n$4\r\nINFO\r\n")).unwrap()
+    );
+    match from_reader::<_, ClusterInfo>(R!(b"*2\r\n$7\r\nCLUSTER\r\n$5\r\nNODES\r\n")) {
+        Err(Error::MismatchedName) => {}
+        _ => panic!("MismatchedName error not found"),
+    }
+}
+
+#[test]
+fn test_unknown_variant() {
+    #[derive(serde::Deserialize, PartialEq, Debug)]
+    enum Test {
+        Get(String),
+        #[serde(rename = "$unknown")]
+        Unknown {
+            name: Vec<u8>,
+            args: Vec<Vec<u8>>,
+        },
+    }
+
+    assert_eq!(
+        Test::Get("k".to_owned()),
+        from_reader(R!(b"*2\r\n$3\r\nGet\r\n$1\r\nk\r\n")).unwrap()
+    );
+    assert_eq!(
+        Test::Unknown {
+            name: b"HELLO".to_vec(),
+            args: vec![b"3".to_vec(), b"".to_vec()],
+        },
+        from_reader(R!(b"*3\r\n$5\r\nHELLO\r\n$1\r\n3\r\n$0\r\n\r\n")).unwrap()
+    );
+
+    #[derive(serde::Deserialize, PartialEq, Debug)]
+    enum Raw {
+        Ping,
+        #[serde(rename = "$unknown")]
+        Unknown(Vec<String>),
+    }
+    assert_eq!(
+        Raw::Unknown(vec!["ECHO".to_owned(), "hi".to_owned()]),
+        from_reader(R!(b"*2\r\n$4\r\nECHO\r\n$2\r\nhi\r\n")).unwrap()
+    );
+}
+
+#[test]
+fn test_unknown_subcommand() {
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    enum Test {
+        #[serde(rename = "CLIENT SETNAME")]
+        ClientSetname(String),
+        #[serde(rename = "$unknown")]
+        Unknown { name: Vec<u8>, args: Vec<Vec<u8>> },
+    }
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    enum Raw {
+        #[serde(rename = "CLIENT SETNAME")]
+        ClientSetname(String),
+        #[serde(rename = "$unknown")]
+        Unknown(Vec<Vec<u8>>),
+    }
+
+    // 为了匹配多个部分的命令名多读的 KILL 仍然是参数，写回时和原来相同
+    let input = b"*3\r\n$6\r\nCLIENT\r\n$4\r\nKILL\r\n$1\r\nx\r\n";
+    let test: Test = from_reader(R!(input)).unwrap();
+    assert_eq!(
+        test,
+        Test::Unknown {
+            name: b"CLIENT".to_vec(),
+            args: vec![b"KILL".to_vec(), b"x".to_vec()],
+        }
+    );
+    assert_eq!(serde_resp::to_bytes(&test).unwrap(), &input[..]);
+
+    let raw: Raw = from_reader(R!(input)).unwrap();
+    assert_eq!(
+        raw,
+        Raw::Unknown(vec![b"CLIENT".to_vec(), b"KILL".to_vec(), b"x".to_vec()])
+    );
+    assert_eq!(serde_resp::to_bytes(&raw).unwrap(), &input[..]);
+}
+
+#[test]
+fn test_iter_skips_failed_frame() {
+    #[derive(serde::Deserialize, PartialEq, Debug)]
+    enum Test {
+        Get(String),
+        Set(String, u32),
+    }
+    // 未知命令、参数类型错误、不是数组的帧，都不影响后续命令的解析
+    let bytes = R!(
+        b"*3\r\n$5\r\nHELLO\r\n$1\r\n3\r\n*2\r\n$1\r\na\r\n$1\r\nb\r\n\
+                     *3\r\n$3\r\nSet\r\n$1\r\nk\r\n$1\r\nx\r\n\
+                     $3\r\nGet\r\n\
+                     *2\r\n$3\r\nGet\r\n$1\r\nk\r\n"
+    );
+    let mut iter = de::Deserializer::from_reader(bytes).into_iter::<Test>();
+    assert!(iter.next().unwrap().is_err());
+    assert!(iter.next().unwrap().is_err());
+    assert!(iter.next().unwrap().is_err());
+    assert_eq!(Test::Get("k".to_owned()), iter.next().unwrap().unwrap());
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn test_bytes() {
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Test {
+        #[serde(with = "serde_resp::bytes")]
+        vec: Vec<u8>,
+        #[serde(with = "serde_resp::bytes")]
+        arr: [u8; 2],
+    }
+
+    let test = Test {
+        vec: b"\x00\r\n\xff".to_vec(),
+        arr: *b"ab",
+    };
+    let bytes = serde_resp::to_bytes(&test).unwrap();
+    assert_eq!(test, from_reader(&bytes[..]).unwrap());
+
+    // 长度和数组不符
+    let r = R!(b"*3\r\n$4\r\nTest\r\n$0\r\n\r\n$3\r\nabc\r\n");
+    assert!(from_reader::<_, Test>(r).is_err());
+}
+
+#[test]
+fn test_nested_option() {
+    use serde::{de::DeserializeOwned, Serialize};
+    use std::fmt::Debug;
+
+    fn round_trip<T>(value: T)
+    where
+        T: Serialize + DeserializeOwned + PartialEq + Debug,
+    {
+        let bytes = serde_resp::to_bytes(&value).unwrap();
+        assert_eq!(value, from_reader::<_, T>(&bytes[..]).unwrap());
+    }
+
+    round_trip::<Option<u8>>(None);
+    round_trip::<Option<u8>>(Some(5));
+    round_trip::<Option<()>>(None);
+    round_trip::<Option<()>>(Some(()));
+    round_trip::<Option<Option<u8>>>(None);
+    round_trip::<Option<Option<u8>>>(Some(None));
+    round_trip::<Option<Option<u8>>>(Some(Some(5)));
+    round_trip::<Option<Option<()>>>(None);
+    round_trip::<Option<Option<()>>>(Some(None));
+    round_trip::<Option<Option<()>>>(Some(Some(())));
+    round_trip::<Option<Option<Option<u8>>>>(None);
+    round_trip::<Option<Option<Option<u8>>>>(Some(None));
+    round_trip::<Option<Option<Option<u8>>>>(Some(Some(None)));
+    round_trip::<Option<Option<Option<u8>>>>(Some(Some(Some(5))));
+    round_trip::<Option<Vec<Option<u8>>>>(None);
+    round_trip::<Option<Vec<Option<u8>>>>(Some(vec![]));
+    round_trip::<Option<Vec<Option<u8>>>>(Some(vec![None, Some(1)]));
+    round_trip::<Vec<Option<Option<u8>>>>(vec![None, Some(None), Some(Some(1))]);
+    round_trip::<(Option<()>, Option<Option<String>>)>((Some(()), Some(None)));
+
+    // null array 和 null bulk string 都是 None
+    assert_eq!(
+        None,
+        from_reader::<_, Option<Vec<u8>>>(R!(b"*-1\r\n")).unwrap()
+    );
+    assert_eq!(None, from_reader::<_, Option<u8>>(R!(b"$-1\r\n")).unwrap());
+    assert_eq!(
+        None,
+        from_reader::<_, Option<Option<u8>>>(R!(b"*-1\r\n")).unwrap()
+    );
+    assert_eq!(
+        Some(None),
+        from_reader::<_, Option<Option<u8>>>(R!(b"*1\r\n*-1\r\n")).unwrap()
+    );
+}
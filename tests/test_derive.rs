@@ -0,0 +1,213 @@
+use serde_resp::command::RespCommand;
+use serde_resp::info::{ArgType, CommandTable};
+use serde_resp::{from_reader, get_keys, to_bytes, Command, Error};
+use serde_resp_derive::RespCommand;
+
+#[derive(RespCommand, Debug, PartialEq)]
+enum Request {
+    #[resp(name = "GET", flags = "readonly fast")]
+    Get(#[resp(key)] String),
+    #[resp(
+        name = "SET",
+        flags = "write denyoom",
+        summary = "Set the string value of a key",
+        group = "string"
+    )]
+    Set {
+        #[resp(key)]
+        key: String,
+        value: Vec<u8>,
+        #[resp(flag)]
+        nx: bool,
+        #[resp(flag)]
+        xx: bool,
+        #[resp(name = "EX")]
+        ex: Option<u64>,
+    },
+    SetEx(#[resp(key)] String, u64, String),
+    Del {
+        #[resp(key, variadic)]
+        keys: Vec<String>,
+    },
+    BLPop {
+        #[resp(key, variadic)]
+        keys: Vec<String>,
+        timeout: u64,
+    },
+    #[resp(name = "CONFIG GET")]
+    ConfigGet(String),
+    Ping(#[resp(optional)] Option<String>),
+}
+
+fn cmd(args: &[&str]) -> Command {
+    let mut cmd = Command::new(args[0]);
+    for arg in &args[1..] {
+        cmd = cmd.arg(*arg);
+    }
+    cmd
+}
+
+fn set(nx: bool, ex: Option<u64>) -> Request {
+    Request::Set {
+        key: "k".to_owned(),
+        value: b"v".to_vec(),
+        nx,
+        xx: false,
+        ex,
+    }
+}
+
+#[test]
+fn test_serialize() {
+    assert_eq!(
+        to_bytes(&set(true, Some(10))).unwrap(),
+        b"*6\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n$2\r\nNX\r\n$2\r\nEX\r\n$2\r\n10\r\n".to_vec()
+    );
+    assert_eq!(
+        to_bytes(&Request::Ping(None)).unwrap(),
+        b"*1\r\n$4\r\nPING\r\n"
+    );
+    let del = Request::Del {
+        keys: vec!["a".to_owned(), "b".to_owned()],
+    };
+    assert_eq!(
+        to_bytes(&del).unwrap(),
+        b"*3\r\n$3\r\nDEL\r\n$1\r\na\r\n$1\r\nb\r\n"
+    );
+    assert_eq!(
+        to_bytes(&Request::ConfigGet("maxmemory".to_owned())).unwrap(),
+        b"*3\r\n$6\r\nCONFIG\r\n$3\r\nGET\r\n$9\r\nmaxmemory\r\n".to_vec()
+    );
+
+    let blpop = Request::BLPop {
+        keys: vec!["a".to_owned(), "b".to_owned()],
+        timeout: 0,
+    };
+    assert_eq!(
+        get_keys(&blpop).unwrap(),
+        vec![b"a".to_vec(), b"b".to_vec()]
+    );
+}
+
+#[test]
+fn test_deserialize() {
+    // 命令名和关键字不区分大小写，关键字的顺序任意
+    let input = b"*6\r\n$3\r\nset\r\n$1\r\nk\r\n$1\r\nv\r\n$2\r\nex\r\n$2\r\n10\r\n$2\r\nNx\r\n";
+    assert_eq!(
+        from_reader::<_, Request>(&input[..]).unwrap(),
+        set(true, Some(10))
+    );
+    assert_eq!(
+        Request::from_command(cmd(&["SET", "k", "v"])).unwrap(),
+        set(false, None)
+    );
+    assert_eq!(
+        Request::from_command(cmd(&["BLPOP", "a", "b", "0"])).unwrap(),
+        Request::BLPop {
+            keys: vec!["a".to_owned(), "b".to_owned()],
+            timeout: 0,
+        }
+    );
+    assert_eq!(
+        Request::from_command(cmd(&["ping", "hi"])).unwrap(),
+        Request::Ping(Some("hi".to_owned()))
+    );
+    assert_eq!(
+        Request::from_command(cmd(&["config", "get", "port"])).unwrap(),
+        Request::ConfigGet("port".to_owned())
+    );
+    for request in [set(false, Some(1)), Request::Ping(None)] {
+        assert_eq!(
+            Request::from_command(request.to_command()).unwrap(),
+            request
+        );
+    }
+}
+
+#[test]
+fn test_errors() {
+    let err = |args: &[&str]| Request::from_command(cmd(args)).unwrap_err();
+    match err(&["SETEX", "k", "10"]) {
+        e @ Error::WrongArity(_) => {
+            assert_eq!(
+                e.to_string(),
+                "wrong number of arguments for 'setex' command"
+            )
+        }
+        e => panic!("{}", e),
+    }
+    assert!(matches!(
+        err(&["SETEX", "k", "ten", "v"]),
+        Error::BadNumContent
+    ));
+    assert!(matches!(err(&["SET", "k", "v", "FOO"]), Error::Syntax));
+    assert!(matches!(
+        err(&["SET", "k", "v", "EX"]),
+        Error::WrongArity(_)
+    ));
+    assert!(matches!(err(&["PING", "a", "b"]), Error::WrongArity(_)));
+    assert!(matches!(err(&["BLPOP", "0"]), Error::WrongArity(_)));
+    assert!(matches!(err(&["DEL"]), Error::WrongArity(_)));
+    match err(&["FOO"]) {
+        Error::UnknownCommand(name) => assert_eq!(name, "FOO"),
+        e => panic!("{}", e),
+    }
+
+    // 通过 serde 反序列化时，错误保持不变
+    let input = b"*3\r\n$5\r\nSETEX\r\n$1\r\nk\r\n$2\r\n10\r\n";
+    match from_reader::<_, Request>(&input[..]) {
+        Err(Error::WrongArity(name)) => assert_eq!(name, "SETEX"),
+        other => panic!("{:?}", other),
+    }
+    let input = b"*4\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n$3\r\nFOO\r\n";
+    assert!(matches!(
+        from_reader::<_, Request>(&input[..]),
+        Err(Error::Syntax)
+    ));
+    let input = b"*1\r\n$3\r\nFOO\r\n";
+    match from_reader::<_, Request>(&input[..]) {
+        Err(Error::UnknownCommand(name)) => assert_eq!(name, "FOO"),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn test_command_infos() {
+    let table = CommandTable::new().register_command::<Request>();
+    let summary: Vec<_> = table
+        .commands()
+        .iter()
+        .map(|c| (&c.name[..], c.arity, c.first_key, c.last_key, c.step))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("get", 2, 1, 1, 1),
+            ("set", -3, 1, 1, 1),
+            ("setex", 4, 1, 1, 1),
+            ("del", -2, 1, -1, 1),
+            ("blpop", -3, 1, -2, 1),
+            ("config|get", 3, 0, 0, 0),
+            ("ping", -1, 0, 0, 0),
+        ]
+    );
+
+    let set = table.get("SET").unwrap();
+    assert_eq!(set.flags, vec!["write", "denyoom"]);
+    assert_eq!(set.group, "string");
+    let args: Vec<_> = set
+        .args
+        .iter()
+        .map(|a| (&a.name[..], a.kind, a.optional, a.token.as_deref()))
+        .collect();
+    assert_eq!(
+        args,
+        vec![
+            ("key", ArgType::Key, false, None),
+            ("value", ArgType::String, false, None),
+            ("nx", ArgType::PureToken, true, Some("NX")),
+            ("xx", ArgType::PureToken, true, Some("XX")),
+            ("ex", ArgType::Integer, true, Some("EX")),
+        ]
+    );
+}
@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use serde_resp::info::{command_infos, ArgType, CommandTable};
+use serde_resp::{from_reader, to_bytes, Integer, Key, Simple};
+
+#[derive(Serialize, Deserialize, Debug)]
+enum Direction {
+    #[serde(rename = "LEFT")]
+    Left,
+    #[serde(rename = "RIGHT")]
+    Right,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename = "LMOVE")]
+struct LMove {
+    source: Key<String>,
+    destination: Key<String>,
+    wherefrom: Direction,
+    whereto: Direction,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum Request {
+    #[serde(rename = "GET")]
+    Get(Key<String>),
+    #[serde(rename = "SET")]
+    Set {
+        key: Key<String>,
+        value: String,
+        ex: Option<u64>,
+    },
+    #[serde(rename = "DEL")]
+    Del(Key<Vec<String>>),
+    #[serde(rename = "MSET")]
+    MSet(Vec<(Key<String>, String)>),
+    #[serde(rename = "CONFIG GET")]
+    ConfigGet(String),
+    #[serde(rename = "PING")]
+    Ping,
+    #[serde(rename = "$unknown")]
+    Unknown(Vec<Vec<u8>>),
+}
+
+#[test]
+fn test_command_infos() {
+    let infos = command_infos::<Request>().unwrap();
+    let summary: Vec<_> = infos
+        .iter()
+        .map(|c| (&c.name[..], c.arity, c.first_key, c.last_key, c.step))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("get", 2, 1, 1, 1),
+            ("set", 4, 1, 1, 1),
+            ("del", 2, 1, 1, 1),
+            ("mset", 2, 0, 0, 0),
+            ("config|get", 3, 0, 0, 0),
+            ("ping", 1, 0, 0, 0),
+        ]
+    );
+
+    let set = &infos[1];
+    let args: Vec<_> = set
+        .args
+        .iter()
+        .map(|a| (&a.name[..], a.kind, a.optional, a.multiple))
+        .collect();
+    assert_eq!(
+        args,
+        vec![
+            ("key", ArgType::Key, false, false),
+            ("value", ArgType::String, false, false),
+            ("ex", ArgType::Integer, false, false),
+        ]
+    );
+
+    // arity 和反序列化时接受的参数个数一致
+    let set = b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n";
+    assert!(from_reader::<_, Request>(&set[..]).is_err());
+    let set = b"*4\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n$-1\r\n";
+    assert!(from_reader::<_, Request>(&set[..]).is_ok());
+
+    let lmove = &command_infos::<LMove>().unwrap()[0];
+    assert_eq!(lmove.name, "lmove");
+    assert_eq!(lmove.arity, 5);
+    assert_eq!((lmove.first_key, lmove.last_key, lmove.step), (1, 2, 1));
+    assert_eq!(lmove.args[2].kind, ArgType::OneOf);
+}
+
+#[test]
+fn test_command_reply() {
+    let mut table = CommandTable::new().register::<Request>().unwrap();
+    assert_eq!(table.len(), 6);
+    table.get_mut("GET").unwrap().flags = vec!["readonly".to_owned(), "fast".to_owned()];
+    assert!(table.get("config get").is_some());
+    assert!(table.get("CONFIG|GET").is_some());
+
+    let info = table.info(&["get", "nosuch"]);
+    assert_eq!(
+        to_bytes(&info).unwrap(),
+        b"*2\r\n\
+        *6\r\n$3\r\nget\r\n:2\r\n*2\r\n+readonly\r\n+fast\r\n:1\r\n:1\r\n:1\r\n\
+        $-1\r\n"
+            .to_vec()
+    );
+
+    let del = table.get_mut("del").unwrap();
+    del.summary = "Delete a key".to_owned();
+    assert_eq!(
+        to_bytes(&table.docs(&["del"])).unwrap(),
+        b"*2\r\n$3\r\ndel\r\n\
+        *6\r\n$7\r\nsummary\r\n$12\r\nDelete a key\r\n$5\r\ngroup\r\n$7\r\ngeneric\r\n\
+        $9\r\narguments\r\n*1\r\n\
+        *4\r\n$4\r\nname\r\n$3\r\nkey\r\n$4\r\ntype\r\n$3\r\nkey\r\n"
+            .to_vec()
+    );
+
+    // 整个 COMMAND 回复是所有命令的数组
+    let all = to_bytes(&table.commands()).unwrap();
+    assert!(all.starts_with(b"*6\r\n*6\r\n$3\r\nget\r\n:2\r\n"));
+}
+
+#[test]
+fn test_line_replies() {
+    assert_eq!(to_bytes(&Integer(-2)).unwrap(), b":-2\r\n");
+    assert_eq!(to_bytes(&Simple("OK")).unwrap(), b"+OK\r\n");
+    assert!(to_bytes(&Simple("a\r\nb")).is_err());
+    assert_eq!(
+        from_reader::<_, Integer>(&b":-2\r\n"[..]).unwrap(),
+        Integer(-2)
+    );
+    assert_eq!(
+        from_reader::<_, Simple<String>>(&b"+OK\r\n"[..]).unwrap(),
+        Simple("OK".to_owned())
+    );
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+use serde_resp::cluster::keys_slot;
+use serde_resp::{from_reader, get_keys, to_bytes, Error, ErrorCode, Key, RedisError};
+use serde_resp_derive::RespCommand;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Request {
+    #[serde(rename = "GET")]
+    Get(Key<String>),
+    #[serde(rename = "SET")]
+    Set { key: Key<String>, value: String },
+    #[serde(rename = "RENAME")]
+    Rename(Key<String>, Key<String>),
+    #[serde(rename = "OBJECT ENCODING")]
+    ObjectEncoding(Key<String>),
+    #[serde(rename = "PING")]
+    Ping,
+}
+
+// 个数不定的 key
+#[derive(RespCommand, Debug, PartialEq)]
+#[resp(name = "DEL")]
+struct Del {
+    #[resp(key, variadic)]
+    keys: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename = "LMOVE")]
+struct LMove {
+    source: Key<String>,
+    destination: Key<String>,
+    wherefrom: String,
+    whereto: String,
+}
+
+fn k(key: &str) -> Key<String> {
+    Key(key.to_owned())
+}
+
+#[test]
+fn test_transparent() {
+    let set = Request::Set {
+        key: k("foo"),
+        value: "bar".to_owned(),
+    };
+    let bytes = to_bytes(&set).unwrap();
+    assert_eq!(bytes, b"*3\r\n$3\r\nSET\r\n$3\r\nfoo\r\n$3\r\nbar\r\n");
+    assert_eq!(from_reader::<_, Request>(&bytes[..]).unwrap(), set);
+
+    let rename = Request::Rename(k("a"), k("b"));
+    let bytes = to_bytes(&rename).unwrap();
+    assert_eq!(bytes, b"*3\r\n$6\r\nRENAME\r\n$1\r\na\r\n$1\r\nb\r\n");
+    assert_eq!(from_reader::<_, Request>(&bytes[..]).unwrap(), rename);
+}
+
+#[test]
+fn test_get_keys() {
+    let keys = |req: &Request| get_keys(req).unwrap();
+    assert_eq!(keys(&Request::Get(k("foo"))), vec![b"foo".to_vec()]);
+    let set = Request::Set {
+        key: k("foo"),
+        value: "bar".to_owned(),
+    };
+    assert_eq!(keys(&set), vec![b"foo".to_vec()]);
+    let del = Del {
+        keys: vec!["a".to_owned(), "b".to_owned()],
+    };
+    assert_eq!(get_keys(&del).unwrap(), vec![b"a".to_vec(), b"b".to_vec()]);
+    let bytes = to_bytes(&del).unwrap();
+    assert_eq!(bytes, b"*3\r\n$3\r\nDEL\r\n$1\r\na\r\n$1\r\nb\r\n");
+    assert_eq!(from_reader::<_, Del>(&bytes[..]).unwrap(), del);
+    assert_eq!(
+        keys(&Request::Rename(k("a"), k("b"))),
+        vec![b"a".to_vec(), b"b".to_vec()]
+    );
+    assert_eq!(
+        keys(&Request::ObjectEncoding(k("foo"))),
+        vec![b"foo".to_vec()]
+    );
+    assert!(keys(&Request::Ping).is_empty());
+
+    let lmove = LMove {
+        source: k("src"),
+        destination: k("dst"),
+        wherefrom: "LEFT".to_owned(),
+        whereto: "RIGHT".to_owned(),
+    };
+    assert_eq!(
+        get_keys(&lmove).unwrap(),
+        vec![b"src".to_vec(), b"dst".to_vec()]
+    );
+}
+
+#[test]
+fn test_keys_slot() {
+    assert_eq!(keys_slot(&Request::Ping).unwrap(), None);
+    assert_eq!(keys_slot(&Request::Get(k("foo"))).unwrap(), Some(12182));
+    let same = Request::Rename(k("{user1000}.a"), k("{user1000}.b"));
+    assert!(keys_slot(&same).unwrap().is_some());
+
+    let cross = Request::Rename(k("a"), k("b"));
+    match keys_slot(&cross) {
+        Err(e @ Error::CrossSlot) => {
+            let reply = RedisError::from(e);
+            assert_eq!(reply.code(), &ErrorCode::CrossSlot);
+            assert_eq!(
+                reply.to_string(),
+                "CROSSSLOT Keys in request don't hash to the same slot"
+            );
+        }
+        other => panic!("expected CrossSlot, got {:?}", other),
+    }
+}
@@ -0,0 +1,244 @@
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+use serde_resp::router::Router;
+use serde_resp::{Command, Error, MultiplexedClient, RedisError};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "ECHO")]
+struct Echo(String);
+
+#[derive(Serialize)]
+#[serde(rename = "FOO")]
+struct Foo;
+
+struct ThreadWaker(thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn waker() -> Waker {
+    Arc::new(ThreadWaker(thread::current())).into()
+}
+
+// 测试用的最简单的执行器
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let waker = waker();
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+fn poll_once<F: Future + Unpin>(future: &mut F) -> Poll<F::Output> {
+    let waker = waker();
+    Pin::new(future).poll(&mut Context::from_waker(&waker))
+}
+
+fn echo_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            thread::spawn(move || {
+                let mut router = Router::new()
+                    .route(|Echo(s)| Ok::<_, RedisError>(s))
+                    .unwrap();
+                let _ = router.serve(stream.try_clone().unwrap(), stream);
+            });
+        }
+    });
+    addr
+}
+
+// 每收到一条命令就通知测试，等测试放行之后才回复
+fn gated_server() -> (SocketAddr, mpsc::Receiver<Command>, mpsc::Sender<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (cmd_tx, cmd_rx) = mpsc::channel();
+    let (go_tx, go_rx) = mpsc::channel::<()>();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut router = Router::new()
+            .route(|Echo(s)| Ok::<_, RedisError>(s))
+            .unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let de = serde_resp::de::Deserializer::from_reader(stream);
+        for cmd in de.into_iter::<Command>() {
+            let cmd = cmd.unwrap();
+            cmd_tx.send(cmd.clone()).unwrap();
+            go_rx.recv().unwrap();
+            std::io::Write::write_all(&mut writer, &router.dispatch(cmd)).unwrap();
+        }
+    });
+    (addr, cmd_rx, go_tx)
+}
+
+#[test]
+fn test_concurrent_tasks() {
+    let client = MultiplexedClient::connect(echo_server()).unwrap();
+    let tasks: Vec<_> = (0..8)
+        .map(|t| {
+            let client = client.clone();
+            thread::spawn(move || {
+                for i in 0..50 {
+                    let s = format!("{}-{}", t, i);
+                    let reply: String = block_on(client.query(&Echo(s.clone()))).unwrap();
+                    assert_eq!(reply, s);
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.join().unwrap();
+    }
+
+    // 一个任务同时等待多个回复
+    let mut futures: Vec<_> = (0..100)
+        .map(|i| client.query::<_, String>(&Echo(i.to_string())))
+        .collect();
+    let mut replies: Vec<_> = futures
+        .iter_mut()
+        .map(|future| match poll_once(future) {
+            Poll::Ready(reply) => Some(reply),
+            Poll::Pending => None,
+        })
+        .collect();
+    for (i, future) in futures.into_iter().enumerate() {
+        let reply = replies[i].take().unwrap_or_else(|| block_on(future));
+        assert_eq!(reply.unwrap(), i.to_string());
+    }
+
+    match block_on(client.query::<_, String>(&Foo)) {
+        Err(Error::Redis(e)) => assert_eq!(e.to_string(), "ERR unknown command 'FOO'"),
+        other => panic!("unexpected {:?}", other),
+    }
+    let reply: Result<String, RedisError> = block_on(client.query(&Foo)).unwrap();
+    assert!(reply.is_err());
+}
+
+#[test]
+fn test_backpressure() {
+    let (addr, commands, go) = gated_server();
+    let client = MultiplexedClient::connect(addr).unwrap();
+    client.set_capacity(1);
+
+    let mut first = client.query::<_, String>(&Echo("a".into()));
+    let mut second = client.query::<_, String>(&Echo("b".into()));
+    assert!(poll_once(&mut first).is_pending());
+    assert_eq!(commands.recv().unwrap().args, vec![b"a".to_vec()]);
+    // 队列已满，第二条命令还没有发送
+    assert!(poll_once(&mut second).is_pending());
+    assert!(commands.try_recv().is_err());
+
+    go.send(()).unwrap();
+    assert_eq!(block_on(first).unwrap(), "a");
+    assert!(poll_once(&mut second).is_pending());
+    assert_eq!(commands.recv().unwrap().args, vec![b"b".to_vec()]);
+    go.send(()).unwrap();
+    assert_eq!(block_on(second).unwrap(), "b");
+}
+
+#[test]
+fn test_cancellation() {
+    let (addr, commands, go) = gated_server();
+    let client = MultiplexedClient::connect(addr).unwrap();
+
+    // 已经发送的命令被丢弃，它的回复不会交给后面的请求
+    let mut cancelled = client.query::<_, String>(&Echo("a".into()));
+    assert!(poll_once(&mut cancelled).is_pending());
+    drop(cancelled);
+    // 还没有发送的命令被丢弃，不会发送
+    drop(client.query::<_, String>(&Echo("x".into())));
+
+    let mut next = client.query::<_, String>(&Echo("b".into()));
+    assert!(poll_once(&mut next).is_pending());
+    go.send(()).unwrap();
+    go.send(()).unwrap();
+    assert_eq!(block_on(next).unwrap(), "b");
+    assert_eq!(commands.recv().unwrap().args, vec![b"a".to_vec()]);
+    assert_eq!(commands.recv().unwrap().args, vec![b"b".to_vec()]);
+}
+
+#[test]
+fn test_connection_closed() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        drop(stream);
+    });
+    let client = MultiplexedClient::connect(addr).unwrap();
+    assert!(block_on(client.query::<_, String>(&Echo("a".into()))).is_err());
+    assert!(matches!(
+        block_on(client.query::<_, String>(&Echo("a".into()))),
+        Err(Error::Io(_))
+    ));
+}
+
+// 被丢弃时通知测试
+struct Tracked<T>(T, &'static str, mpsc::Sender<&'static str>);
+
+impl<T> Drop for Tracked<T> {
+    fn drop(&mut self) {
+        let _ = self.2.send(self.1);
+    }
+}
+
+impl<T: Read> Read for Tracked<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl<T: Write> Write for Tracked<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[test]
+fn test_drop_joins_threads() {
+    let addr = echo_server();
+    let stream = TcpStream::connect(addr).unwrap();
+    let handle = stream.try_clone().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let client = MultiplexedClient::new(
+        Tracked(stream.try_clone().unwrap(), "reader", tx.clone()),
+        Tracked(stream, "writer", tx),
+        move || {
+            let _ = handle.shutdown(Shutdown::Both);
+        },
+    );
+    assert_eq!(
+        block_on(client.query::<_, String>(&Echo("a".into()))).unwrap(),
+        "a"
+    );
+    // 读线程阻塞在 read 上，丢弃客户端之后两个线程都已经退出
+    let other = client.clone();
+    drop(client);
+    assert!(rx.try_recv().is_err());
+    drop(other);
+    let mut dropped: Vec<_> = rx.try_iter().collect();
+    dropped.sort();
+    assert_eq!(dropped, vec!["reader", "writer"]);
+}
@@ -0,0 +1,162 @@
+use std::io::Write;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use serde_resp::de::Deserializer;
+use serde_resp::{Command, Error, Pool};
+
+// PING 回复 PONG，ECHO 原样返回，QUIT 之后关闭连接，BAD 回复一个损坏的帧
+fn mock_server() -> (SocketAddr, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let counter = accepted.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let stream = stream.unwrap();
+            thread::spawn(move || {
+                let mut writer = stream.try_clone().unwrap();
+                for cmd in Deserializer::from_reader(stream).into_iter::<Command>() {
+                    let cmd = match cmd {
+                        Ok(cmd) => cmd,
+                        Err(_) => return,
+                    };
+                    let reply = match &cmd.name[..] {
+                        b"PING" => b"+PONG\r\n".to_vec(),
+                        b"ECHO" => {
+                            let arg = &cmd.args[0];
+                            [format!("${}\r\n", arg.len()).as_bytes(), arg, b"\r\n"].concat()
+                        }
+                        b"BAD" => b"?\r\n".to_vec(),
+                        b"QUIT" => {
+                            let _ = writer.write_all(b"+OK\r\n");
+                            return;
+                        }
+                        _ => b"-ERR unknown command\r\n".to_vec(),
+                    };
+                    writer.write_all(&reply).unwrap();
+                }
+            });
+        }
+    });
+    (addr, accepted)
+}
+
+fn echo(s: &str) -> Command {
+    Command::new("ECHO").arg(s)
+}
+
+#[test]
+fn test_reuse() {
+    let (addr, accepted) = mock_server();
+    let pool = Pool::tcp(addr).unwrap();
+    let tasks: Vec<_> = (0..4)
+        .map(|t| {
+            let pool = pool.clone();
+            thread::spawn(move || {
+                for i in 0..20 {
+                    let s = format!("{}-{}", t, i);
+                    let reply: String = pool.get().unwrap().query(&echo(&s)).unwrap();
+                    assert_eq!(reply, s);
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.join().unwrap();
+    }
+    assert!(pool.size() <= 4);
+    assert_eq!(pool.idle(), pool.size());
+    assert_eq!(accepted.load(Ordering::SeqCst), pool.size());
+}
+
+#[test]
+fn test_max_size() {
+    let (addr, _) = mock_server();
+    let pool = Pool::tcp(addr).unwrap();
+    pool.set_max_size(1);
+    pool.set_wait_timeout(Some(Duration::from_millis(50)));
+
+    let client = pool.get().unwrap();
+    match pool.get() {
+        Err(Error::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::TimedOut),
+        _ => panic!("expected timeout"),
+    }
+
+    pool.set_wait_timeout(None);
+    let waiter = {
+        let pool = pool.clone();
+        thread::spawn(move || pool.get().unwrap().query::<_, String>(&echo("a")).unwrap())
+    };
+    thread::sleep(Duration::from_millis(20));
+    drop(client);
+    assert_eq!(waiter.join().unwrap(), "a");
+    assert_eq!(pool.size(), 1);
+
+    // 很长的等待时间相当于一直等待
+    pool.set_wait_timeout(Some(Duration::MAX));
+    let client = pool.get().unwrap();
+    let waiter = {
+        let pool = pool.clone();
+        thread::spawn(move || pool.get().unwrap().query::<_, String>(&echo("b")).unwrap())
+    };
+    thread::sleep(Duration::from_millis(20));
+    drop(client);
+    assert_eq!(waiter.join().unwrap(), "b");
+}
+
+#[test]
+fn test_discard_broken() {
+    let (addr, accepted) = mock_server();
+    let pool = Pool::tcp(addr).unwrap();
+
+    let mut client = pool.get().unwrap();
+    assert!(client.query::<_, String>(&Command::new("BAD")).is_err());
+    assert!(client.is_broken());
+    drop(client);
+    assert_eq!(pool.size(), 0);
+
+    // 还有回复没有读取
+    let mut client = pool.get().unwrap();
+    client.send(&echo("a")).unwrap();
+    drop(client);
+    assert_eq!(pool.size(), 0);
+
+    let mut client = pool.get().unwrap();
+    assert_eq!(client.query::<_, String>(&echo("b")).unwrap(), "b");
+    client.discard();
+    assert_eq!(pool.size(), 0);
+    assert_eq!(accepted.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn test_idle_and_health_check() {
+    let (addr, accepted) = mock_server();
+    let pool = Pool::tcp(addr).unwrap();
+
+    // 服务端已经关闭的连接，PING 检查失败后重新连接
+    let mut client = pool.get().unwrap();
+    assert_eq!(
+        client.query::<_, String>(&Command::new("QUIT")).unwrap(),
+        "OK"
+    );
+    drop(client);
+    assert_eq!(pool.idle(), 1);
+    let mut client = pool.get().unwrap();
+    assert_eq!(client.query::<_, String>(&echo("a")).unwrap(), "a");
+    drop(client);
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    assert_eq!(pool.size(), 1);
+
+    // 空闲太久的连接直接丢弃
+    pool.set_idle_timeout(Some(Duration::from_millis(20)));
+    thread::sleep(Duration::from_millis(40));
+    let mut client = pool.get().unwrap();
+    assert_eq!(client.query::<_, String>(&echo("b")).unwrap(), "b");
+    assert_eq!(accepted.load(Ordering::SeqCst), 3);
+    assert_eq!(pool.size(), 1);
+}
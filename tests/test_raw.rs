@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+use serde_resp::de::Deserializer;
+use serde_resp::{from_reader, to_bytes, Error, Key, RawFrame, RawFrameRef};
+
+const FRAMES: &[&[u8]] = &[
+    b"+OK\r\n",
+    b"-ERR unknown command 'FOO'\r\n",
+    b":-42\r\n",
+    b"$-1\r\n",
+    b"$5\r\nhe\r\no\r\n",
+    b"*-1\r\n",
+    b"*0\r\n",
+    b"*2\r\n*2\r\n:1\r\n$-1\r\n*1\r\n*0\r\n",
+    // 非规范的长度也原样保留
+    b"*02\r\n$003\r\nabc\r\n:007\r\n",
+];
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "SET")]
+struct Set {
+    key: Key<String>,
+    value: RawFrame,
+}
+
+#[test]
+fn test_raw_frame() {
+    for frame in FRAMES {
+        let raw: RawFrame = from_reader(*frame).unwrap();
+        assert_eq!(raw.as_bytes(), *frame);
+        assert_eq!(to_bytes(&raw).unwrap(), *frame);
+        assert_eq!(to_bytes(&RawFrameRef::new(frame).unwrap()).unwrap(), *frame);
+    }
+
+    // 一个流中连续的多个帧
+    let pipeline = FRAMES.concat();
+    let frames: Vec<_> = Deserializer::from_reader(&pipeline[..])
+        .into_iter::<RawFrame>()
+        .map(|raw| raw.unwrap().into_bytes())
+        .collect();
+    assert_eq!(frames, FRAMES);
+}
+
+#[test]
+fn test_inner_frame() {
+    let input = b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n*2\r\n$1\r\na\r\n:1\r\n";
+    let set: Set = from_reader(&input[..]).unwrap();
+    assert_eq!(set.value.as_bytes(), b"*2\r\n$1\r\na\r\n:1\r\n");
+    assert_eq!(to_bytes(&set).unwrap(), &input[..]);
+
+    let items: Vec<RawFrame> = from_reader(&b"*3\r\n+OK\r\n$-1\r\n*1\r\n:1\r\n"[..]).unwrap();
+    let items: Vec<_> = items.iter().map(|raw| raw.as_bytes()).collect();
+    assert_eq!(items, vec![&b"+OK\r\n"[..], b"$-1\r\n", b"*1\r\n:1\r\n"]);
+
+    // Option 已经 peek 了头部，仍然得到完整的原始字节
+    let raw: Option<RawFrame> = from_reader(&b"$03\r\nabc\r\n"[..]).unwrap();
+    assert_eq!(raw.unwrap().as_bytes(), b"$03\r\nabc\r\n");
+    let raw: Option<RawFrame> = from_reader(&b"$-1\r\n"[..]).unwrap();
+    assert_eq!(raw, None);
+    assert_eq!(to_bytes(&None::<RawFrame>).unwrap(), b"$-1\r\n");
+
+    // Some 中的帧原样写出，只有 null 帧读回来是 None
+    for frame in FRAMES {
+        let some = Some(RawFrame::new(frame.to_vec()).unwrap());
+        let bytes = to_bytes(&some).unwrap();
+        assert_eq!(bytes, *frame);
+        let expected = match *frame {
+            b"$-1\r\n" | b"*-1\r\n" => None,
+            _ => some,
+        };
+        assert_eq!(
+            from_reader::<_, Option<RawFrame>>(&bytes[..]).unwrap(),
+            expected
+        );
+    }
+
+    // 保存的帧可以之后再解析
+    let reply: Vec<Option<String>> = RawFrame::new(b"*2\r\n$1\r\na\r\n$-1\r\n".to_vec())
+        .unwrap()
+        .decode()
+        .unwrap();
+    assert_eq!(reply, vec![Some("a".to_owned()), None]);
+}
+
+#[test]
+fn test_invalid_frame() {
+    assert!(matches!(
+        RawFrame::new(b"+OK\r\n+OK\r\n".to_vec()),
+        Err(Error::TrailingBytes)
+    ));
+    assert!(matches!(RawFrameRef::new(b"*2\r\n:1\r\n"), Err(Error::Eof)));
+    assert!(matches!(
+        RawFrameRef::new(b"?\r\n"),
+        Err(Error::UnknownFrameType)
+    ));
+
+    // 帧本身损坏时无法跳过，迭代停止
+    let input = b"*2\r\n$1\r\na\r\n$2\r\nab\n\n+OK\r\n";
+    let mut iter = Deserializer::from_reader(&input[..]).into_iter::<RawFrame>();
+    assert!(matches!(iter.next(), Some(Err(Error::UnbalancedCRLF))));
+    assert!(iter.next().is_none());
+    // integer 的内容错误时，帧的边界仍然确定，可以跳过
+    let input = b"*2\r\n:x\r\n:1\r\n:y\r\n:2\r\n";
+    let mut iter = Deserializer::from_reader(&input[..]).into_iter::<RawFrame>();
+    assert!(matches!(iter.next(), Some(Err(Error::BadNumContent))));
+    assert!(matches!(iter.next(), Some(Err(Error::BadNumContent))));
+    assert_eq!(iter.next().unwrap().unwrap().as_bytes(), b":2\r\n");
+    assert!(iter.next().is_none());
+}
@@ -0,0 +1,148 @@
+use serde::Deserialize;
+
+use serde_resp::de::Deserializer;
+use serde_resp::{Error, Event, Reader};
+
+#[test]
+fn test_events() {
+    let input = b"*4\r\n+OK\r\n-ERR bad\r\n:-42\r\n*2\r\n$3\r\nfoo\r\n$-1\r\n*-1\r\n*0\r\n";
+    let mut reader = Reader::new(&input[..]);
+    let mut events = vec![];
+    let mut depths = vec![];
+    while let Some(event) = reader.next_event().unwrap() {
+        events.push(event);
+        depths.push(reader.depth());
+    }
+    assert_eq!(
+        events,
+        vec![
+            Event::ArrayStart(4),
+            Event::SimpleString(b"OK".to_vec()),
+            Event::Error(b"ERR bad".to_vec()),
+            Event::Integer(-42),
+            Event::ArrayStart(2),
+            Event::Bulk(b"foo".to_vec()),
+            Event::Null,
+            Event::Null,
+            Event::ArrayStart(0),
+        ]
+    );
+    assert_eq!(depths, vec![1, 1, 1, 1, 2, 2, 0, 0, 0]);
+    assert_eq!(reader.bytes_offset(), input.len());
+
+    // 帧中间读完是错误
+    let mut reader = Reader::new(&b"*2\r\n:1\r\n"[..]);
+    assert_eq!(reader.next_event().unwrap(), Some(Event::ArrayStart(2)));
+    assert_eq!(reader.next_event().unwrap(), Some(Event::Integer(1)));
+    assert!(matches!(reader.next_event(), Err(Error::Eof)));
+
+    let mut reader = Reader::new(&b":1x\r\n"[..]);
+    assert!(matches!(reader.next_event(), Err(Error::BadNumContent)));
+}
+
+#[test]
+fn test_chunks() {
+    let value = vec![b'x'; 10000];
+    let mut input = format!("*3\r\n${}\r\n", value.len()).into_bytes();
+    input.extend_from_slice(&value);
+    input.extend_from_slice(b"\r\n$2\r\nab\r\n$5\r\nhello\r\n");
+
+    let mut reader = Reader::new(&input[..]);
+    reader.set_chunk_size(Some(4));
+    assert_eq!(reader.next_event().unwrap(), Some(Event::ArrayStart(3)));
+    assert_eq!(reader.next_event().unwrap(), Some(Event::BulkStart(10000)));
+    let mut buf = [0; 300];
+    let mut read = vec![];
+    loop {
+        let n = reader.read_chunk(&mut buf).unwrap();
+        if n == 0 {
+            break;
+        }
+        read.extend_from_slice(&buf[..n]);
+    }
+    assert_eq!(read, value);
+    // 没有超过 chunk_size 的 bulk string 完整读取
+    assert_eq!(
+        reader.next_event().unwrap(),
+        Some(Event::Bulk(b"ab".to_vec()))
+    );
+    // 没有读完的内容被跳过
+    assert_eq!(reader.next_event().unwrap(), Some(Event::BulkStart(5)));
+    assert_eq!(reader.read_chunk(&mut buf[..2]).unwrap(), 2);
+    assert_eq!(&buf[..2], b"he");
+    assert_eq!(reader.next_event().unwrap(), None);
+    assert_eq!(reader.bytes_offset(), input.len());
+
+    let mut reader = Reader::new(&b"$5\r\nhello\n\n"[..]);
+    reader.set_chunk_size(Some(0));
+    assert_eq!(reader.next_event().unwrap(), Some(Event::BulkStart(5)));
+    assert!(matches!(
+        reader.read_chunk(&mut buf),
+        Err(Error::UnbalancedCRLF)
+    ));
+}
+
+#[test]
+fn test_stream_scan_reply() {
+    // SCAN 的回复：下一次的游标和一批 key，逐个反序列化而不是一次读入整个列表
+    let mut input = b"*2\r\n$2\r\n17\r\n*1000\r\n".to_vec();
+    for i in 0..1000 {
+        let key = format!("key:{}", i);
+        input.extend_from_slice(format!("${}\r\n{}\r\n", key.len(), key).as_bytes());
+    }
+    input.extend_from_slice(b"+OK\r\n");
+
+    let mut de = Deserializer::new(Reader::new(&input[..]));
+    assert_eq!(
+        de.reader_mut().next_event().unwrap(),
+        Some(Event::ArrayStart(2))
+    );
+    let cursor = u64::deserialize(&mut de).unwrap();
+    assert_eq!(cursor, 17);
+    let len = match de.reader_mut().next_event().unwrap() {
+        Some(Event::ArrayStart(len)) => len,
+        other => panic!("unexpected {:?}", other),
+    };
+    let mut count = 0;
+    for i in 0..len {
+        let key = String::deserialize(&mut de).unwrap();
+        assert_eq!(key, format!("key:{}", i));
+        count += 1;
+    }
+    assert_eq!(count, 1000);
+    assert_eq!(de.reader_mut().depth(), 0);
+
+    // 后续的帧仍然可以用 serde 读取
+    assert_eq!(String::deserialize(&mut de).unwrap(), "OK");
+    assert_eq!(de.into_reader().next_event().unwrap(), None);
+}
+
+#[test]
+fn test_read_bulk_to() {
+    let value = vec![b'x'; 100_000];
+    let mut input = format!("*4\r\n${}\r\n", value.len()).into_bytes();
+    input.extend_from_slice(&value);
+    input.extend_from_slice(b"\r\n+OK\r\n$-1\r\n:1\r\n");
+
+    let mut de = Deserializer::from_reader(&input[..]);
+    assert_eq!(
+        de.reader_mut().next_event().unwrap(),
+        Some(Event::ArrayStart(4))
+    );
+    let mut output = vec![];
+    assert_eq!(de.read_bulk_to(&mut output).unwrap(), Some(value.len()));
+    assert_eq!(output, value);
+    let mut output = vec![];
+    assert_eq!(de.read_bulk_to(&mut output).unwrap(), Some(2));
+    assert_eq!(output, b"OK");
+    assert_eq!(de.read_bulk_to(&mut output).unwrap(), None);
+    assert_eq!(i64::deserialize(&mut de).unwrap(), 1);
+
+    let mut reader = Reader::new(&b"*1\r\n$1\r\na\r\n"[..]);
+    assert!(matches!(
+        reader.read_bulk_to(&mut vec![]),
+        Err(Error::ExpectedDollarSign)
+    ));
+    let mut reader = Reader::new(&b"$5\r\nhel"[..]);
+    assert!(matches!(reader.read_bulk_to(&mut vec![]), Err(Error::Eof)));
+}
@@ -0,0 +1,138 @@
+use serde_resp::{de, from_reader, to_bytes, to_reply, ErrorCode, RedisError};
+
+type Reply<T> = Result<T, RedisError>;
+
+#[test]
+fn test_ok_replies() {
+    let r: Reply<String> = from_reader(&b"+OK\r\n"[..]).unwrap();
+    assert_eq!(r, Ok("OK".to_owned()));
+    let r: Reply<String> = from_reader(&b"$5\r\nhello\r\n"[..]).unwrap();
+    assert_eq!(r, Ok("hello".to_owned()));
+    let r: Reply<i64> = from_reader(&b":-42\r\n"[..]).unwrap();
+    assert_eq!(r, Ok(-42));
+    let r: Reply<Option<String>> = from_reader(&b"$-1\r\n"[..]).unwrap();
+    assert_eq!(r, Ok(None));
+    let r: Reply<Vec<String>> = from_reader(&b"*2\r\n$1\r\na\r\n+b\r\n"[..]).unwrap();
+    assert_eq!(r, Ok(vec!["a".to_owned(), "b".to_owned()]));
+}
+
+#[test]
+fn test_error_replies() {
+    let bytes = b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n";
+    let err = from_reader::<_, Reply<String>>(&bytes[..])
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(err.code(), &ErrorCode::WrongType);
+    assert_eq!(
+        err.message(),
+        "Operation against a key holding the wrong kind of value"
+    );
+
+    let err = from_reader::<_, Reply<i64>>(&b"-MOVED 3999 127.0.0.1:6381\r\n"[..])
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(err.code(), &ErrorCode::Moved);
+    assert_eq!(err.message(), "3999 127.0.0.1:6381");
+    assert_eq!(err.to_string(), "MOVED 3999 127.0.0.1:6381");
+
+    assert_eq!(
+        RedisError::parse(b"NOTBUSY No scripts in execution right now."),
+        RedisError::new(
+            ErrorCode::Other("NOTBUSY".to_owned()),
+            "No scripts in execution right now."
+        )
+    );
+    assert_eq!(
+        RedisError::parse(b"Error without code"),
+        RedisError::new(ErrorCode::Err, "Error without code")
+    );
+
+    // 错误回复也可以反序列化成字符串
+    let r: Result<(), String> = from_reader(&b"-ERR unknown command\r\n"[..]).unwrap();
+    assert_eq!(r, Err("ERR unknown command".to_owned()));
+}
+
+#[test]
+fn test_reply_pipeline() {
+    let bytes = b"+OK\r\n-NOSCRIPT No matching script\r\n$2\r\nhi\r\n";
+    let replies: Vec<Reply<String>> = de::Deserializer::from_reader(&bytes[..])
+        .into_iter()
+        .collect::<Result<_, serde_resp::Error>>()
+        .unwrap();
+    assert_eq!(replies[0], Ok("OK".to_owned()));
+    assert_eq!(
+        replies[1].as_ref().unwrap_err().code(),
+        &ErrorCode::NoScript
+    );
+    assert_eq!(replies[2], Ok("hi".to_owned()));
+}
+
+#[test]
+fn test_error_to_reply() {
+    #[allow(dead_code)]
+    #[derive(serde::Deserialize, Debug)]
+    enum Request {
+        Get(String),
+    }
+    #[allow(dead_code)]
+    #[derive(serde::Deserialize, Debug)]
+    #[serde(rename = "SET")]
+    struct Set {
+        key: String,
+        value: String,
+    }
+
+    let err = from_reader::<_, Request>(&b"*2\r\n$3\r\nfoo\r\n$1\r\na\r\n"[..]).unwrap_err();
+    assert_eq!(
+        to_reply::<(), _>(&Err(RedisError::from(err))).unwrap(),
+        b"-ERR unknown command 'foo'\r\n"
+    );
+
+    let err = from_reader::<_, Request>(&b"*1\r\n$3\r\nGet\r\n"[..]).unwrap_err();
+    assert_eq!(
+        RedisError::from(err).to_string(),
+        "ERR wrong number of arguments for 'get' command"
+    );
+
+    let err = from_reader::<_, Set>(&b"*2\r\n$3\r\nSET\r\n$1\r\nk\r\n"[..]).unwrap_err();
+    assert_eq!(
+        to_reply::<(), _>(&Err(err)).unwrap(),
+        b"-ERR wrong number of arguments for 'set' command\r\n"
+    );
+}
+
+#[test]
+fn test_serialize_reply() {
+    let ok: Reply<String> = Ok("v".to_owned());
+    assert_eq!(to_bytes(&ok).unwrap(), b"$1\r\nv\r\n");
+    assert_eq!(to_reply(&ok).unwrap(), b"$1\r\nv\r\n");
+
+    let err: Reply<String> = Err(RedisError::new(
+        ErrorCode::NoScript,
+        "No matching\r\nscript",
+    ));
+    assert_eq!(
+        to_bytes(&err).unwrap(),
+        b"-NOSCRIPT No matching  script\r\n"
+    );
+    assert_eq!(
+        to_bytes(&RedisError::new(ErrorCode::Busy, "")).unwrap(),
+        b"-BUSY\r\n"
+    );
+
+    let err: Result<u32, String> = Err("something went wrong".to_owned());
+    assert_eq!(to_bytes(&err).unwrap(), b"-ERR something went wrong\r\n");
+    assert_eq!(to_reply(&err).unwrap(), b"-ERR something went wrong\r\n");
+
+    // 错误回复可以原样解析回来
+    let bytes = to_bytes(&Err::<(), _>(RedisError::new(
+        ErrorCode::WrongType,
+        "wrong kind",
+    )))
+    .unwrap();
+    let back: Reply<()> = from_reader(&bytes[..]).unwrap();
+    assert_eq!(
+        back,
+        Err(RedisError::new(ErrorCode::WrongType, "wrong kind"))
+    );
+}
@@ -0,0 +1,170 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+use serde_resp::router::{Handler, Router};
+use serde_resp::{Command, Error, ErrorCode, Integer, RedisError, Simple};
+use serde_resp_derive::RespCommand;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "GET")]
+struct Get(String);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "SET")]
+struct Set(String, String);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "CONFIG GET")]
+struct ConfigGet(String);
+
+#[derive(RespCommand)]
+enum Counter {
+    Incr(#[resp(key)] String),
+    Decr(#[resp(key)] String),
+}
+
+type Store = Rc<RefCell<HashMap<String, String>>>;
+
+struct CounterHandler(Store);
+
+impl Handler<Counter> for CounterHandler {
+    type Reply = Integer;
+
+    fn handle(&mut self, cmd: Counter) -> Result<Integer, RedisError> {
+        let (key, delta) = match cmd {
+            Counter::Incr(key) => (key, 1),
+            Counter::Decr(key) => (key, -1),
+        };
+        let mut store = self.0.borrow_mut();
+        let value = store.entry(key).or_insert_with(|| "0".to_owned());
+        let n: i64 = value.parse().map_err(|_| {
+            RedisError::new(ErrorCode::Err, "value is not an integer or out of range")
+        })?;
+        *value = (n + delta).to_string();
+        Ok(Integer(n + delta))
+    }
+}
+
+fn router() -> Router {
+    let store = Store::default();
+    let (get_store, set_store) = (store.clone(), store.clone());
+    Router::new()
+        .route(move |Get(key)| Ok(get_store.borrow().get(&key).cloned()))
+        .unwrap()
+        .route(move |Set(key, value)| {
+            set_store.borrow_mut().insert(key, value);
+            Ok(Simple("OK"))
+        })
+        .unwrap()
+        .route(|ConfigGet(_)| -> Result<Vec<String>, RedisError> { Ok(vec![]) })
+        .unwrap()
+        .route_command(CounterHandler(store))
+}
+
+#[test]
+fn test_dispatch() {
+    let mut router = router();
+    let cmd = |args: &[&str]| {
+        let mut cmd = Command::new(args[0]);
+        for arg in &args[1..] {
+            cmd = cmd.arg(*arg);
+        }
+        cmd
+    };
+    assert_eq!(router.dispatch(cmd(&["GET", "k"])), b"$-1\r\n");
+    assert_eq!(router.dispatch(cmd(&["set", "k", "v"])), b"+OK\r\n");
+    assert_eq!(router.dispatch(cmd(&["Get", "k"])), b"$1\r\nv\r\n");
+    assert_eq!(router.dispatch(cmd(&["config", "GET", "x"])), b"*0\r\n");
+    assert_eq!(router.dispatch(cmd(&["INCR", "n"])), b":1\r\n");
+    assert_eq!(router.dispatch(cmd(&["DECR", "n"])), b":0\r\n");
+    assert_eq!(
+        router.dispatch(cmd(&["INCR", "k"])),
+        b"-ERR value is not an integer or out of range\r\n"
+    );
+    assert_eq!(
+        router.dispatch(cmd(&["FOO"])),
+        b"-ERR unknown command 'FOO'\r\n"
+    );
+    assert_eq!(
+        router.dispatch(cmd(&["SET", "k"])),
+        b"-ERR wrong number of arguments for 'set' command\r\n"
+    );
+    assert_eq!(
+        router.dispatch(cmd(&["INCR"])),
+        b"-ERR wrong number of arguments for 'incr' command\r\n"
+    );
+    assert_eq!(router.table().len(), 5);
+}
+
+#[test]
+fn test_serve() {
+    let mut router = router();
+    let input = b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n\
+        *2\r\n$3\r\nGET\r\n$1\r\nk\r\n\
+        *1\r\n$3\r\nFOO\r\n\
+        *2\r\n$4\r\nINCR\r\n$1\r\nn\r\n";
+    let mut output = vec![];
+    router.serve(&input[..], &mut output).unwrap();
+    assert_eq!(
+        output,
+        b"+OK\r\n$1\r\nv\r\n-ERR unknown command 'FOO'\r\n:1\r\n".to_vec()
+    );
+
+    // 不是 bulk string 的命令只影响这一帧
+    let input = b"*2\r\n$3\r\nGET\r\n*1\r\n$1\r\nk\r\n*2\r\n$3\r\nGET\r\n$1\r\nk\r\n";
+    let mut output = vec![];
+    router.serve(&input[..], &mut output).unwrap();
+    assert!(output.starts_with(b"-ERR "));
+    assert!(output.ends_with(b"\r\n$1\r\nv\r\n"));
+
+    // 数据流损坏时回复协议错误并停止
+    let input = b"*1\r\n$3\r\nGETX\r\n*2\r\n$3\r\nGET\r\n$1\r\nk\r\n";
+    let mut output = vec![];
+    assert!(router.serve(&input[..], &mut output).is_err());
+    assert!(output.starts_with(b"-ERR Protocol error: "));
+
+    // 在一帧的中间关闭连接不是正常结束，之前的命令仍然得到回复
+    for input in &[
+        &b"*2\r\n$3\r\nGET\r\n$1\r\nk\r\n*2\r\n$3\r\nGET\r\n"[..],
+        b"*2\r\n$3\r\nGET\r\n$1\r\nk\r\n*2\r\n$3\r\nGE",
+        b"*2\r\n$3\r\nGET\r\n$1\r\nk\r\n*2",
+    ] {
+        let mut output = vec![];
+        assert!(matches!(router.serve(*input, &mut output), Err(Error::Eof)));
+        assert_eq!(output, b"$1\r\nv\r\n");
+    }
+}
+
+// 记录 flush 的次数
+#[derive(Default)]
+struct Counted {
+    output: Vec<u8>,
+    flushes: usize,
+}
+
+impl std::io::Write for Counted {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.output.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_serve_pipeline_flush() {
+    // 一次读到的 pipeline 只在最后 flush 一次
+    let mut router = router();
+    let input = b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n\
+        *2\r\n$3\r\nGET\r\n$1\r\nk\r\n\
+        *2\r\n$4\r\nINCR\r\n$1\r\nn\r\n";
+    let mut output = Counted::default();
+    router.serve(&input[..], &mut output).unwrap();
+    assert_eq!(output.output, b"+OK\r\n$1\r\nv\r\n:1\r\n".to_vec());
+    assert_eq!(output.flushes, 1);
+}
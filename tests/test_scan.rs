@@ -0,0 +1,132 @@
+use std::io::BufReader;
+
+use serde::Deserialize;
+
+use serde_resp::de::Deserializer;
+use serde_resp::scan::{copy_frame, scan_frame_with_limits, skip_frame};
+use serde_resp::{from_reader, scan_frame, Command, Error, Limits};
+
+const FRAMES: &[&[u8]] = &[
+    b"+OK\r\n",
+    b"-ERR unknown command 'FOO'\r\n",
+    b":-42\r\n",
+    b"$-1\r\n",
+    b"$0\r\n\r\n",
+    b"$5\r\nhe\r\no\r\n",
+    b"*-1\r\n",
+    b"*0\r\n",
+    b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n",
+    b"*2\r\n*2\r\n:1\r\n$-1\r\n*1\r\n*0\r\n",
+];
+
+#[test]
+fn test_scan_frame() {
+    for frame in FRAMES {
+        assert_eq!(scan_frame(frame).unwrap(), Some(frame.len()));
+        // 每一个前缀都不完整
+        for len in 0..frame.len() {
+            assert_eq!(scan_frame(&frame[..len]).unwrap(), None, "{:?}", frame);
+        }
+    }
+
+    // 连续的多个帧
+    let pipeline = FRAMES.concat();
+    let mut pos = 0;
+    let mut count = 0;
+    while let Some(len) = scan_frame(&pipeline[pos..]).unwrap() {
+        pos += len;
+        count += 1;
+    }
+    assert_eq!((pos, count), (pipeline.len(), FRAMES.len()));
+}
+
+#[test]
+fn test_scan_invalid() {
+    let cases: &[(&[u8], Error)] = &[
+        (b"?\r\n", Error::UnknownFrameType),
+        (b"+OK\n", Error::UnbalancedCRLF),
+        (b"*x\r\n", Error::BadLengthHint),
+        (b"$-2\r\n", Error::BadLengthHint),
+        (b"$2\r\nabc\r\n", Error::ExpectedLF),
+        (b"$2\r\nab\n\n", Error::UnbalancedCRLF),
+        (b"*2\r\n+OK\r\n!", Error::UnknownFrameType),
+        (b":abc\r\n", Error::BadNumContent),
+        (b"*2\r\n:1\r\n:\r\n", Error::BadNumContent),
+    ];
+    for (input, expected) in cases {
+        let e = scan_frame(input).unwrap_err();
+        assert_eq!(e.to_string(), expected.to_string(), "{:?}", input);
+        let e = skip_frame(&mut &input[..], &Limits::default()).unwrap_err();
+        assert_eq!(e.to_string(), expected.to_string(), "{:?}", input);
+    }
+}
+
+#[test]
+fn test_limits() {
+    let limits = Limits {
+        max_bulk_len: 4,
+        max_array_len: 2,
+        max_depth: 2,
+        max_line_len: 8,
+    };
+    let ok: &[&[u8]] = &[
+        b"$4\r\nabcd\r\n",
+        b"*2\r\n*1\r\n:1\r\n:2\r\n",
+        b"+12345678\r\n",
+    ];
+    let too_large: &[&[u8]] = &[
+        b"$5\r\n",
+        b"*3\r\n",
+        b"*1\r\n*1\r\n*1\r\n",
+        b"+123456789\r\n",
+    ];
+    for input in ok {
+        assert_eq!(
+            scan_frame_with_limits(input, &limits).unwrap(),
+            Some(input.len())
+        );
+        assert_eq!(
+            skip_frame(&mut &input[..], &limits).unwrap(),
+            Some(input.len())
+        );
+        let mut de = Deserializer::from_reader(&input[..]);
+        de.set_limits(limits);
+        assert!(serde::de::IgnoredAny::deserialize(&mut de).is_ok());
+    }
+    for input in too_large {
+        assert!(matches!(
+            scan_frame_with_limits(input, &limits),
+            Err(Error::LimitExceeded(_))
+        ));
+        assert!(matches!(
+            skip_frame(&mut &input[..], &limits),
+            Err(Error::LimitExceeded(_))
+        ));
+    }
+
+    // 默认的限制下，巨大的长度不会导致分配内存
+    let result: Result<Command, _> = from_reader(&b"*1\r\n$1073741824\r\n"[..]);
+    assert!(matches!(result, Err(Error::LimitExceeded(_))));
+}
+
+#[test]
+fn test_copy_frame() {
+    let pipeline = FRAMES.concat();
+    let mut reader = BufReader::with_capacity(3, &pipeline[..]);
+    let mut output = vec![];
+    let mut lens = vec![];
+    while let Some(len) = copy_frame(&mut reader, &mut output, &Limits::default()).unwrap() {
+        lens.push(len);
+    }
+    assert_eq!(output, pipeline);
+    let expected: Vec<_> = FRAMES.iter().map(|f| f.len()).collect();
+    assert_eq!(lens, expected);
+
+    // 帧不完整
+    for frame in FRAMES {
+        for len in 1..frame.len() {
+            let result = skip_frame(&mut &frame[..len], &Limits::default());
+            assert!(matches!(result, Err(Error::Eof)), "{:?}", &frame[..len]);
+        }
+    }
+}
@@ -51,7 +51,128 @@ fn test_float_fail() {
     }
 }
 
+#[test]
+fn test_map_fail() {
+    let mut m = std::collections::HashMap::new();
+    m.insert("k", "v");
+    match to_bytes(&m) {
+        Err(Error::Message(msg)) => assert_eq!(msg, "map is not supported"),
+        _ => assert!(false, "no error when serializing map"),
+    }
+}
+
 #[test]
 fn test_char() {
     assert_eq!(to_bytes(&'🌟').unwrap(), "$4\r\n🌟\r\n".as_bytes());
 }
+
+#[test]
+fn test_multi_word_name() {
+    #[derive(serde::Serialize)]
+    enum Test {
+        #[serde(rename = "CLIENT SETNAME")]
+        ClientSetname(String),
+        #[serde(rename = "CONFIG GET")]
+        ConfigGet { parameter: String },
+    }
+
+    let c = Test::ClientSetname("conn".to_owned());
+    assert_eq!(
+        to_bytes(&c).unwrap(),
+        b"*3\r\n$6\r\nCLIENT\r\n$7\r\nSETNAME\r\n$4\r\nconn\r\n"
+    );
+
+    let c = Test::ConfigGet {
+        parameter: "save".to_owned(),
+    };
+    assert_eq!(
+        to_bytes(&c).unwrap(),
+        b"*3\r\n$6\r\nCONFIG\r\n$3\r\nGET\r\n$4\r\nsave\r\n"
+    );
+
+    #[derive(serde::Serialize)]
+    #[serde(rename = "CLUSTER INFO")]
+    struct ClusterInfo;
+    assert_eq!(
+        to_bytes(&ClusterInfo).unwrap(),
+        b"*2\r\n$7\r\nCLUSTER\r\n$4\r\nINFO\r\n"
+    );
+}
+
+#[test]
+fn test_unknown_variant() {
+    #[derive(serde::Serialize)]
+    enum Test {
+        #[serde(rename = "$unknown")]
+        Unknown { name: Vec<u8>, args: Vec<Vec<u8>> },
+    }
+
+    let u = Test::Unknown {
+        name: b"HELLO".to_vec(),
+        args: vec![b"3".to_vec()],
+    };
+    assert_eq!(to_bytes(&u).unwrap(), b"*2\r\n$5\r\nHELLO\r\n$1\r\n3\r\n");
+
+    // 空的参数原样写回
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    enum Raw {
+        #[serde(rename = "$unknown")]
+        Unknown(Vec<Vec<u8>>),
+    }
+    let input = b"*3\r\n$5\r\nHELLO\r\n$0\r\n\r\n$1\r\n3\r\n";
+    let raw: Raw = serde_resp::from_reader(&input[..]).unwrap();
+    assert_eq!(
+        raw,
+        Raw::Unknown(vec![b"HELLO".to_vec(), vec![], b"3".to_vec()])
+    );
+    assert_eq!(to_bytes(&raw).unwrap(), &input[..]);
+    let u = Test::Unknown {
+        name: b"HELLO".to_vec(),
+        args: vec![vec![], b"3".to_vec(), vec![]],
+    };
+    assert_eq!(
+        to_bytes(&u).unwrap(),
+        b"*4\r\n$5\r\nHELLO\r\n$0\r\n\r\n$1\r\n3\r\n$0\r\n\r\n"
+    );
+    let u = Test::Unknown {
+        name: b"PING".to_vec(),
+        args: vec![],
+    };
+    assert_eq!(to_bytes(&u).unwrap(), b"*1\r\n$4\r\nPING\r\n");
+}
+
+#[test]
+fn test_bytes() {
+    #[derive(serde::Serialize)]
+    struct Test {
+        #[serde(with = "serde_resp::bytes")]
+        vec: Vec<u8>,
+        #[serde(with = "serde_resp::bytes")]
+        arr: [u8; 2],
+        seq: Vec<u8>,
+    }
+
+    let test = Test {
+        vec: b"\x00\xff".to_vec(),
+        arr: *b"ab",
+        seq: vec![7],
+    };
+    assert_eq!(
+        to_bytes(&test).unwrap(),
+        &b"*4\r\n$4\r\nTest\r\n$2\r\n\x00\xff\r\n$2\r\nab\r\n*1\r\n$1\r\n7\r\n"[..]
+    );
+}
+
+#[test]
+fn test_nested_option() {
+    assert_eq!(to_bytes(&None::<u8>).unwrap(), b"$-1\r\n");
+    assert_eq!(to_bytes(&Some(1u8)).unwrap(), b"$1\r\n1\r\n");
+    assert_eq!(to_bytes(&Some(())).unwrap(), b"*1\r\n$-1\r\n");
+    assert_eq!(to_bytes(&Some(None::<u8>)).unwrap(), b"*1\r\n$-1\r\n");
+    assert_eq!(to_bytes(&Some(Some(1u8))).unwrap(), b"*1\r\n$1\r\n1\r\n");
+    assert_eq!(
+        to_bytes(&Some(Some(None::<u8>))).unwrap(),
+        b"*1\r\n*1\r\n$-1\r\n"
+    );
+    assert_eq!(to_bytes(&Some(vec![None::<u8>])).unwrap(), b"*1\r\n$-1\r\n");
+}
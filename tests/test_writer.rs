@@ -0,0 +1,256 @@
+use std::io::{self, IoSlice, Write};
+
+use serde::Serialize;
+
+use serde_resp::{
+    from_reader, to_bytes, to_writer, to_writer_vectored, write_command_vectored, Command, Error,
+    Event, Reader, Simple, VectoredWriter, Writer,
+};
+
+#[test]
+fn test_write_tokens() {
+    let mut writer = Writer::new(vec![]);
+    writer.write_array_header(7).unwrap();
+    writer.write_bulk(b"he\r\no").unwrap();
+    writer.write_integer(-42).unwrap();
+    writer.write_simple(b"OK").unwrap();
+    writer.write_error("unknown command 'foo'").unwrap();
+    writer.write_error("WRONGTYPE bad\r\nvalue").unwrap();
+    writer.write_null().unwrap();
+    writer.write_null_array().unwrap();
+    let output = writer.into_inner();
+    assert_eq!(
+        output,
+        &b"*7\r\n$5\r\nhe\r\no\r\n:-42\r\n+OK\r\n-ERR unknown command 'foo'\r\n\
+           -WRONGTYPE bad  value\r\n$-1\r\n*-1\r\n"[..]
+    );
+
+    // 写出的数据可以被 Reader 读回
+    let mut reader = Reader::new(&output[..]);
+    let mut events = vec![];
+    while let Some(event) = reader.next_event().unwrap() {
+        events.push(event);
+    }
+    assert_eq!(events[1], Event::Bulk(b"he\r\no".to_vec()));
+    assert_eq!(events[2], Event::Integer(-42));
+    assert_eq!(events.len(), 8);
+
+    // simple string 的检查和 Serializer 相同
+    let mut writer = Writer::new(vec![]);
+    let e = writer.write_simple(b"a\r\nb").unwrap_err();
+    assert!(writer.get_ref().is_empty());
+    let expected = to_bytes(&Simple("a\r\nb")).unwrap_err();
+    assert_eq!(e.to_string(), expected.to_string());
+}
+
+struct Failing;
+
+impl io::Write for Failing {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename = "SET")]
+struct Set<'a> {
+    key: &'a str,
+    value: Option<i64>,
+}
+
+#[test]
+fn test_to_writer() {
+    let set = Set {
+        key: "k",
+        value: Some(10),
+    };
+    let mut output = vec![];
+    to_writer(&mut output, &set).unwrap();
+    assert_eq!(output, to_bytes(&set).unwrap());
+    assert_eq!(output, &b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$2\r\n10\r\n"[..]);
+
+    let mut output = io::Cursor::new(vec![]);
+    to_writer(&mut output, &Command::new("PING")).unwrap();
+    assert_eq!(output.into_inner(), b"*1\r\n$4\r\nPING\r\n");
+
+    assert!(matches!(to_writer(Failing, &set), Err(Error::Io(_))));
+}
+
+#[test]
+fn test_write_bulk_from() {
+    let mut writer = Writer::new(vec![]);
+    writer.write_array_header(3).unwrap();
+    writer.write_bulk(b"SET").unwrap();
+    writer.write_bulk(b"k").unwrap();
+    writer.write_bulk_from(io::repeat(b'x'), 100_000).unwrap();
+    let output = writer.into_inner();
+    let mut expected = b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$100000\r\n".to_vec();
+    expected.extend(vec![b'x'; 100_000]);
+    expected.extend_from_slice(b"\r\n");
+    assert_eq!(output, expected);
+    assert_eq!(
+        from_reader::<_, Command>(&output[..]).unwrap(),
+        Command::new("SET").arg("k").arg(vec![b'x'; 100_000])
+    );
+
+    // reader 的数据不够
+    let mut writer = Writer::new(vec![]);
+    assert!(matches!(
+        writer.write_bulk_from(&b"abc"[..], 5),
+        Err(Error::Eof)
+    ));
+}
+
+// 记录写入的内容和每一块数据的地址，每次最多写入 limit 个字节
+struct Recorder {
+    output: Vec<u8>,
+    pointers: Vec<*const u8>,
+    limit: usize,
+    calls: usize,
+}
+
+impl io::Write for Recorder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_vectored(&[IoSlice::new(buf)])
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.calls += 1;
+        let mut written = 0;
+        for buf in bufs {
+            let n = buf.len().min(self.limit - written);
+            self.pointers.push(buf.as_ptr());
+            self.output.extend_from_slice(&buf[..n]);
+            written += n;
+            if written == self.limit {
+                break;
+            }
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_vectored() {
+    let cmd = Command::new("SET").arg("k").arg(vec![b'x'; 100_000]);
+    for &limit in &[usize::MAX, 7] {
+        let mut recorder = Recorder {
+            output: vec![],
+            pointers: vec![],
+            limit,
+            calls: 0,
+        };
+        to_writer_vectored(&mut recorder, &cmd).unwrap();
+        assert_eq!(recorder.output, to_bytes(&cmd).unwrap());
+        // 很大的参数直接从 Command 中写出，没有被复制
+        assert!(recorder.pointers.contains(&cmd.args[1].as_ptr()));
+    }
+
+    // 所有内容都小于 threshold 时，只有 flush 时写出一次
+    let mut writer = VectoredWriter::new(Recorder {
+        output: vec![],
+        pointers: vec![],
+        limit: usize::MAX,
+        calls: 0,
+    });
+    let set = Set {
+        key: "k",
+        value: Some(10),
+    };
+    to_writer(&mut writer, &set).unwrap();
+    to_writer(&mut writer, &set).unwrap();
+    assert!(writer.get_ref().pointers.is_empty());
+    let recorder = writer.into_inner().unwrap();
+    assert_eq!(recorder.pointers.len(), 1);
+    assert_eq!(recorder.output, to_bytes(&set).unwrap().repeat(2));
+
+    let mut writer = VectoredWriter::new(vec![]);
+    writer.set_threshold(0);
+    to_writer(&mut writer, &set).unwrap();
+    assert_eq!(writer.into_inner().unwrap(), to_bytes(&set).unwrap());
+}
+
+#[test]
+fn test_vectored_command() {
+    let big = vec![b'x'; 100_000];
+    let cmd = Command::new("MSET")
+        .arg("a")
+        .arg(&big[..])
+        .arg("b")
+        .arg(&big[..]);
+    let mut recorder = Recorder {
+        output: vec![],
+        pointers: vec![],
+        limit: usize::MAX,
+        calls: 0,
+    };
+    write_command_vectored(&mut recorder, &cmd.borrowed()).unwrap();
+    assert_eq!(recorder.output, to_bytes(&cmd).unwrap());
+    // 两个很大的参数在同一次 write_vectored 中写出，都没有被复制
+    assert_eq!(recorder.calls, 1);
+    assert!(recorder.pointers.contains(&cmd.args[1].as_ptr()));
+    assert!(recorder.pointers.contains(&cmd.args[3].as_ptr()));
+
+    // 丢弃时写出剩下的内容
+    let mut output = vec![];
+    {
+        let mut writer = VectoredWriter::new(&mut output);
+        writer.write_all(b"+OK\r\n").unwrap();
+        writer.write_borrowed(&big).unwrap();
+    }
+    assert_eq!(output.len(), 5 + big.len());
+}
+
+// 写入 budget 个字节之后返回错误
+struct Limited {
+    output: Vec<u8>,
+    budget: usize,
+}
+
+impl io::Write for Limited {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.budget == 0 {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"));
+        }
+        let n = buf.len().min(self.budget);
+        self.output.extend_from_slice(&buf[..n]);
+        self.budget -= n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_vectored_partial() {
+    let big = vec![b'x'; 10_000];
+    // 写出了 data 的一部分时返回写出的字节数
+    let mut writer = VectoredWriter::new(Limited {
+        output: vec![],
+        budget: 5 + 100,
+    });
+    writer.write_all(b"hello").unwrap();
+    assert_eq!(writer.write(&big).unwrap(), 100);
+    assert!(writer.write(&big[100..]).is_err());
+    assert_eq!(writer.get_ref().output.len(), 105);
+
+    // 缓冲区都没有写完时返回错误，剩下的部分还在缓冲区中
+    let mut writer = VectoredWriter::new(Limited {
+        output: vec![],
+        budget: 3,
+    });
+    writer.write_all(b"hello").unwrap();
+    assert!(writer.write(&big).is_err());
+    assert_eq!(writer.get_ref().output, b"hel");
+    assert!(writer.flush().is_err());
+}